@@ -0,0 +1,173 @@
+use spin::Mutex;
+use super::stack_trace::capture_stack;
+use super::stack_trace::print_stack;
+
+// Debug builds record the order in which globals are locked (see
+// utility/global). Locking a global while holding another creates a
+// dependency between them. If two globals are ever locked in both
+// orders, two threads (or a thread and an interrupt handler) can each
+// hold one while waiting for the other, so both orders are reported.
+// Locking a global that the same thread already holds is reported too
+//
+// Nothing here is allocated on the heap, because growing the heap
+// locks the FRAME_ALLOCATOR and ACTIVE_PAGE_TABLE
+
+const MAX_CLASSES: usize = 32;
+const MAX_HELD: usize = 16;
+pub const TRACE_DEPTH: usize = 8;
+
+pub type Trace = [usize; TRACE_DEPTH];
+
+#[derive(Copy, Clone)]
+struct HeldLock {
+	class: usize,
+	/// The thread that locked the global
+	/// Interrupt handlers lock on behalf of the interrupted thread
+	owner: usize,
+	trace: Trace,
+}
+
+/// A problem found when a global is about to be locked
+#[derive(Debug, PartialEq)]
+pub enum Violation {
+	/// The global is already held by the same thread
+	Recursion { class: usize, previous: Trace },
+	/// The global was previously locked before the held global
+	Inversion { class: usize, held: usize, previous: Trace },
+}
+
+pub struct LockDependencies {
+	classes: [Option<&'static str>; MAX_CLASSES],
+	held: [Option<HeldLock>; MAX_HELD],
+	/// Where the second global was first locked while holding the first
+	dependencies: [[Option<Trace>; MAX_CLASSES]; MAX_CLASSES],
+	reported: [[bool; MAX_CLASSES]; MAX_CLASSES],
+}
+
+// Printing a report locks the display globals, which are
+// not recorded while the dependencies are locked
+static LOCK_DEPENDENCIES: Mutex<LockDependencies> = Mutex::new(LockDependencies::new());
+
+/// Records that the global is about to be locked
+pub fn acquire(identifier: &'static str) {
+	::interrupts::functions::without_interrupts(|| {
+		let mut dependencies = match LOCK_DEPENDENCIES.try_lock() {
+			Some(dependencies) => dependencies,
+			None => return,
+		};
+
+		let mut trace = [0; TRACE_DEPTH];
+		capture_stack(&mut trace);
+
+		let owner = ::task::functions::active_thread_id();
+		let violation = dependencies.acquire(identifier, owner, trace);
+		match violation {
+			Some(Violation::Recursion { class, previous }) => {
+				report(&dependencies, "Recursive locking", class, class, &previous, &trace);
+				panic!("Global locked while already held: {}", identifier);
+			}
+			Some(Violation::Inversion { class, held, previous }) =>
+				report(&dependencies, "Lock order inversion", class, held, &previous, &trace),
+			None => (),
+		}
+	})
+}
+
+/// Records that the global has been unlocked
+pub fn release(identifier: &'static str) {
+	::interrupts::functions::without_interrupts(|| {
+		if let Some(mut dependencies) = LOCK_DEPENDENCIES.try_lock() {
+			dependencies.release(identifier, ::task::functions::active_thread_id());
+		}
+	})
+}
+
+impl LockDependencies {
+	pub const fn new() -> LockDependencies {
+		LockDependencies {
+			classes: [None; MAX_CLASSES],
+			held: [None; MAX_HELD],
+			dependencies: [[None; MAX_CLASSES]; MAX_CLASSES],
+			reported: [[false; MAX_CLASSES]; MAX_CLASSES],
+		}
+	}
+
+	/// Records that the owner is about to lock the global
+	///
+	/// Each inversion is only returned the first time it is found
+	pub fn acquire(&mut self, identifier: &'static str, owner: usize, trace: Trace) -> Option<Violation> {
+		let class = self.class(identifier)?;
+		let mut violation = None;
+		for index in 0..MAX_HELD {
+			let held = match self.held[index] {
+				Some(held) if held.owner == owner => held,
+				_ => continue,
+			};
+
+			if held.class == class {
+				return Some(Violation::Recursion { class, previous: held.trace });
+			}
+
+			if let Some(inverse) = self.dependencies[class][held.class] {
+				if !self.reported[class][held.class] && violation.is_none() {
+					self.reported[class][held.class] = true;
+					violation = Some(Violation::Inversion { class, held: held.class, previous: inverse });
+				}
+			}
+
+			if self.dependencies[held.class][class].is_none() {
+				self.dependencies[held.class][class] = Some(trace);
+			}
+		}
+
+		// Globals that are locked while too many others are
+		// held are not recorded rather than reported wrongly
+		if let Some(slot) = self.held.iter_mut().find(|slot| slot.is_none()) {
+			*slot = Some(HeldLock { class, owner, trace });
+		}
+		violation
+	}
+
+	/// Records that the global has been unlocked
+	pub fn release(&mut self, identifier: &'static str, owner: usize) {
+		let class = match self.classes.iter().position(|class| *class == Some(identifier)) {
+			Some(class) => class,
+			None => return,
+		};
+
+		// A global can be unlocked by a different thread than the one that
+		// locked it if it was held across a context switch. See switch_thread
+		let is_class = |slot: &Option<HeldLock>| slot.map_or(false, |held| held.class == class);
+		let index = self.held.iter().rposition(|slot| is_class(slot) && slot.unwrap().owner == owner)
+		                .or_else(|| self.held.iter().rposition(&is_class));
+		if let Some(index) = index {
+			self.held[index] = None;
+		}
+	}
+
+	/// Returns the class of the global or None if there are too many
+	pub fn class(&mut self, identifier: &'static str) -> Option<usize> {
+		if let Some(class) = self.classes.iter().position(|class| *class == Some(identifier)) {
+			return Some(class);
+		}
+
+		let class = self.classes.iter().position(Option::is_none)?;
+		self.classes[class] = Some(identifier);
+		Some(class)
+	}
+}
+
+fn report(dependencies: &LockDependencies, kind: &str, first: usize, second: usize, previous: &Trace, current: &Trace) {
+	let identifier = |class: usize| dependencies.classes[class].unwrap_or("?");
+	eprintln!("\n{}: {} and {}", kind, identifier(first), identifier(second));
+	eprintln!("Previously locked at:");
+	print_stack(captured(previous));
+	eprintln!("Now locked at:");
+	print_stack(captured(current));
+}
+
+/// The part of the trace that was filled by capture_stack
+fn captured(trace: &Trace) -> &[usize] {
+	let length = trace.iter().position(|address| *address == 0).unwrap_or(TRACE_DEPTH);
+	&trace[..length]
+}
@@ -2,3 +2,5 @@ pub use self::stack_trace::stack_trace;
 
 pub mod stack_trace;
 pub mod symbols;
+#[cfg(debug_assertions)]
+pub mod lockdep;
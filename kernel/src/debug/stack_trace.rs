@@ -33,20 +33,68 @@ pub extern fn stack_trace() {
 		let return_address = unsafe { *(base_pointer.offset(1)) } as usize;
 		let return_address = VirtualAddress::new(return_address);
 
-		// If we haven't loaded the symbol table yet just
-		// print the raw return address
-		if let Some(symbols) = symbols {
-			show_function_call(return_address, symbols);
-		} else {
-			println!("    Call site: {:#?}", return_address);
-		}
+		show_call_site(return_address, symbols);
 
 		// The pushed base pointer is the address to the previous stack frame
 		base_pointer = unsafe { (*base_pointer) as *const usize };
 	}
 }
 
-fn show_function_call(address: VirtualAddress, symbols: &BTreeMap<VirtualAddress, Demangle>) {
+/// Fills the trace with the return addresses of the calling function
+/// and its callers and returns how many were found
+///
+/// Unlike stack_trace, the stack is only walked while the frames are
+/// on the page of the first frame, so the frame pointers of user mode
+/// code or of a thread's first frame are never followed
+pub fn capture_stack(trace: &mut [usize]) -> usize {
+	use paging::Page;
+	use paging::PageLike;
+
+	let mut base_pointer: usize;
+	unsafe { asm!("mov rax, rbp" : "={rax}"(base_pointer) ::: "intel") }
+	let page = base_pointer & !(Page::SIZE as usize - 1);
+
+	let mut count = 0;
+	while count < trace.len() {
+		let on_page = base_pointer & !(Page::SIZE as usize - 1) == page &&
+			base_pointer + 2 * ::core::mem::size_of::<usize>() <= page + Page::SIZE as usize;
+		if base_pointer % ::core::mem::align_of::<usize>() != 0 || !on_page {
+			break;
+		}
+
+		let frame = base_pointer as *const usize;
+		trace[count] = unsafe { *frame.offset(1) };
+		count += 1;
+
+		// Frames of callers are always above the frame of the function
+		let next = unsafe { *frame };
+		if next <= base_pointer {
+			break;
+		}
+		base_pointer = next;
+	}
+	count
+}
+
+/// Prints return addresses that were captured by capture_stack
+pub fn print_stack(trace: &[usize]) {
+	let symbols = super::symbols::SYMBOL_TABLE.try();
+	for return_address in trace {
+		show_call_site(VirtualAddress::new(*return_address), symbols);
+	}
+}
+
+fn show_call_site(address: VirtualAddress, symbols: Option<&BTreeMap<VirtualAddress, Demangle>>) {
+	// If we haven't loaded the symbol table yet just
+	// print the raw return address
+	let symbols = match symbols {
+		Some(symbols) => symbols,
+		None => {
+			println!("    Call site: {:#?}", address);
+			return;
+		}
+	};
+
 	// The address of every instruction in a function is
 	// after the address of the function itself. Thus,
 	// we find the symbol with the greatest address that's
@@ -4,8 +4,9 @@ use super::drivers::GlobalFacade;
 use super::printers::ScrollPrinter;
 use super::drivers::vga_text_driver::VgaTextDriver;
 
-pub static SYSTEM_DISPLAY: Global<VgaTextDriver> = Global::new("SYSTEM_DISPLAY");
-pub static SYSTEM_PRINTER: Global<ScrollPrinter<BootBuffer<GlobalFacade>>> = Global::new("SYSTEM_PRINTER");
+// Interrupt handlers print when they panic
+pub static SYSTEM_DISPLAY: Global<VgaTextDriver> = Global::new_irq_safe("SYSTEM_DISPLAY");
+pub static SYSTEM_PRINTER: Global<ScrollPrinter<BootBuffer<GlobalFacade>>> = Global::new_irq_safe("SYSTEM_PRINTER");
 
 pub fn initialize() {
 	SYSTEM_DISPLAY.set(unsafe { VgaTextDriver::new() });
@@ -3,5 +3,5 @@ use super::LocationSlice;
 use super::Resource;
 
 pub trait Provider {
-	fn open(&mut self, location: &LocationSlice) -> Option<Box<Resource>>;
+	fn open(&mut self, location: &LocationSlice) -> Option<Box<Resource + Send>>;
 }
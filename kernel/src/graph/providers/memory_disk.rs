@@ -90,7 +90,7 @@ impl MemoryDisk {
 }
 
 impl Provider for MemoryDisk {
-	fn open(&mut self, location: &LocationSlice) -> Option<Box<Resource>> {
+	fn open(&mut self, location: &LocationSlice) -> Option<Box<Resource + Send>> {
 		if let Some(last) = location.try_last() {
 			let resource = self.files.get(last)?.clone();
 			return Some(box MemoryFile::new(resource));
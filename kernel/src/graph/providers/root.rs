@@ -19,7 +19,7 @@ impl Root {
 }
 
 impl Provider for Root {
-	fn open(&mut self, location: &LocationSlice) -> Option<Box<Resource>> {
+	fn open(&mut self, location: &LocationSlice) -> Option<Box<Resource + Send>> {
 		let (first, rest) = location.split()?;
 		self.providers.get_mut(first)?.open(&rest)
 	}
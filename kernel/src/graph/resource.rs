@@ -5,10 +5,8 @@ pub trait Resource {
 	fn write(&mut self, buffer: &[u8]) -> ResourceResult<()>;
 	fn seek(&mut self, count: usize) -> ResourceResult<usize>;
 	fn close(&mut self) -> ResourceResult<()>;
-}
 
-impl Resource {
-	pub fn read_all(&mut self) -> ::alloc::Vec<u8> {
+	fn read_all(&mut self) -> ::alloc::Vec<u8> {
 		let mut data = ::alloc::Vec::new();
 		let mut buffer = [0];
 		while let Ok(count) = self.read(&mut buffer) {
@@ -1,3 +1,5 @@
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
 use spin::Once;
 use structures::FixedStack;
 use super::GdtDescriptor;
@@ -8,10 +10,20 @@ use x86_64::structures::tss::TaskStateSegment;
 
 static GDT: Once<super::Gdt> = Once::new();
 static IDT: Once<Idt> = Once::new();
-pub static TSS: Global<TaskStateSegment> = Global::new("TASK_STATE_SEGMENT");
+pub static TSS: Global<TaskStateSegment> = Global::new_irq_safe("TASK_STATE_SEGMENT");
+
+// The number of interrupt handlers that are running. Globals that are
+// not interrupt safe must not be locked by an interrupt handler
+static INTERRUPT_DEPTH: AtomicUsize = AtomicUsize::new(0);
 
 // These user selectors are used when creating an initial
 // user mode stack. See task/loaders/stack::create_initial_stack
+// The kernel code selector is needed for the syscall instruction.
+// See system_call/functions::initialize
+// The kernel selectors are also used to resume a thread in kernel
+// mode. See task/loaders/stack::create_kernel_stack
+pub static KERNEL_CODE_SELECTOR: Once<u16> = Once::new();
+pub static KERNEL_DATA_SELECTOR: Once<u16> = Once::new();
 pub static USER_CODE_SELECTOR: Once<u16> = Once::new();
 pub static USER_DATA_SELECTOR: Once<u16> = Once::new();
 
@@ -32,6 +44,7 @@ const KEYBOARD_INTERRUPT_INDEX: usize = 1;
 // We have shifted the interrupt vectors up 32 so the actual
 // index in the interrupt table is 0xaa - 32
 const SYSTEM_CALL_INDEX: usize = 0xaa - super::pic_functions::PIC_ONE_VECTOR_BASE as usize;
+const YIELD_INDEX: usize = 0xab - super::pic_functions::PIC_ONE_VECTOR_BASE as usize;
 
 pub fn initialize() {
 	let _status = ::display::text_mode::BootStatus::new("Initializing interrupt descriptor table");
@@ -50,6 +63,61 @@ pub fn post_initialize() {
 	unsafe { ::x86_64::instructions::interrupts::enable(); }
 }
 
+pub fn interrupts_enabled() -> bool {
+	const INTERRUPT_FLAG: u64 = 1 << 9;
+	let flags: u64;
+	unsafe { asm!("pushfq; pop $0" : "=r"(flags) ::: "intel", "volatile"); }
+	flags & INTERRUPT_FLAG != 0
+}
+
+/// Runs the closure with interrupts disabled
+///
+/// Interrupt safe globals (see utility/global) disable interrupts
+/// themselves, but locking several globals inside this function
+/// also prevents a context switch in between
+pub fn without_interrupts<F, R>(f: F) -> R where F: FnOnce() -> R {
+	let _guard = disable_interrupts();
+	f()
+}
+
+/// Disables interrupts until the guard is dropped
+pub fn disable_interrupts() -> InterruptGuard {
+	let enabled = interrupts_enabled();
+	if enabled { unsafe { ::x86_64::instructions::interrupts::disable(); } }
+	InterruptGuard { enabled }
+}
+
+/// Restores the interrupt flag to its state before disable_interrupts
+pub struct InterruptGuard {
+	enabled: bool,
+}
+
+impl Drop for InterruptGuard {
+	fn drop(&mut self) {
+		if self.enabled { unsafe { ::x86_64::instructions::interrupts::enable(); } }
+	}
+}
+
+/// Marks an interrupt handler as running until the guard is dropped
+pub fn enter_interrupt() -> InterruptContext {
+	INTERRUPT_DEPTH.fetch_add(1, Ordering::SeqCst);
+	InterruptContext { _private: () }
+}
+
+pub fn in_interrupt() -> bool {
+	INTERRUPT_DEPTH.load(Ordering::SeqCst) > 0
+}
+
+pub struct InterruptContext {
+	_private: (),
+}
+
+impl Drop for InterruptContext {
+	fn drop(&mut self) {
+		INTERRUPT_DEPTH.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
 fn initialize_global_descriptor_table() {
 	use core::ops::Deref;
 
@@ -65,13 +133,19 @@ fn initialize_global_descriptor_table() {
 		// and user mode. See GdtDescriptor
 		kernel_code_selector = gdt.add_kernel_entry(GdtDescriptor::kernel_code_segment());
 		kernel_data_selector = gdt.add_kernel_entry(GdtDescriptor::kernel_data_segment());
-		user_code_selector = gdt.add_user_entry(GdtDescriptor::user_code_segment());
+
+		// The sysret instruction expects the user data segment to be
+		// directly before the user code segment, so the order of
+		// these entries must not be changed
 		user_data_selector = gdt.add_user_entry(GdtDescriptor::user_data_segment());
+		user_code_selector = gdt.add_user_entry(GdtDescriptor::user_code_segment());
 		tss_selector = gdt.add_kernel_entry(GdtDescriptor::tss_segment(&TSS.lock().deref()));
 		gdt
 	});
 	gdt.load();
 
+	KERNEL_CODE_SELECTOR.call_once(|| kernel_code_selector.0);
+	KERNEL_DATA_SELECTOR.call_once(|| kernel_data_selector.0);
 	USER_CODE_SELECTOR.call_once(|| user_code_selector.0);
 	USER_DATA_SELECTOR.call_once(|| user_data_selector.0);
 
@@ -118,9 +192,19 @@ unsafe fn set_interrupt_handlers(table: &mut Idt) {
 	table.general_protection_fault.set_handler_fn(general_fault_handler)
 	     .set_stack_index(GENERAL_FAULT_STACK_INDEX as u16);
 	table.invalid_opcode.set_handler_fn(invalid_opcode_handler);
-	table.interrupts[TIMER_INTERRUPT_INDEX].set_handler_fn(timer_handler);
+	table.device_not_available.set_handler_fn(device_not_available_handler);
 	table.interrupts[KEYBOARD_INTERRUPT_INDEX].set_handler_fn(keyboard_handler);
 
+	// The thread switching handlers are naked functions so that the
+	// registers of a thread are saved in a known layout
+	let timer_handler = ::core::mem::transmute(timer_handler as unsafe extern "C" fn());
+	table.interrupts[TIMER_INTERRUPT_INDEX].set_handler_fn(timer_handler);
+
+	// Kernel code uses this interrupt to switch threads immediately
+	// See task/functions::yield_now
+	let yield_handler = ::core::mem::transmute(yield_handler as unsafe extern "C" fn());
+	table.interrupts[YIELD_INDEX].set_handler_fn(yield_handler);
+
 	// We allow interrupts so the scheduler can preempt a system call
 	// We need the privilege level to be Ring3 so user mode
 	// threads can use
@@ -134,6 +218,11 @@ unsafe fn set_interrupt_handlers(table: &mut Idt) {
 	// but they allow interrupts to occur while their handler is
 	// executing. In this case, the system call handler would be
 	// considered a trap.
+	//
+	// The system call handler is a naked function because it needs
+	// direct access to the registers of the calling thread, so it
+	// is converted into the type expected by the table here.
+	let system_call_handler = ::core::mem::transmute(system_call_handler as unsafe extern "C" fn());
 	table.interrupts[SYSTEM_CALL_INDEX]
 		.set_handler_fn(system_call_handler)
 		.disable_interrupts(false)
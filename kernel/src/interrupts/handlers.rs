@@ -1,8 +1,19 @@
 use super::send_interrupt_end;
+use task::signal;
 use x86_64::structures::idt::ExceptionStackFrame;
 use x86_64::structures::idt::PageFaultErrorCode;
 
+// Faults in user mode raise a signal in the active thread
+// Faults in kernel mode are bugs, so they panic
+fn is_user_mode(stack_frame: &ExceptionStackFrame) -> bool {
+	stack_frame.code_segment & 0b11 == 0b11
+}
+
 pub extern "x86-interrupt" fn zero_divide_handler(stack_frame: &mut ExceptionStackFrame) {
+	if is_user_mode(stack_frame) {
+		let _context = super::functions::enter_interrupt();
+		return signal::on_user_fault(stack_frame, signal::SIGFPE);
+	}
 	panic!("\nDivide by zero: {:#?}", stack_frame);
 }
 
@@ -19,61 +30,369 @@ pub extern "x86-interrupt" fn page_fault_handler(stack_frame: &mut ExceptionStac
 	// page fault when a page fault occurs
 	let address = ::x86_64::registers::control_regs::cr2();
 	let address = ::paging::VirtualAddress::new(address.0);
-	if !::memory::functions::handle_heap_fault(address, &error_code) {
+	let _context = super::functions::enter_interrupt();
+	if !::memory::functions::handle_heap_fault(address.clone(), &error_code) &&
+		!::paging::copy_on_write::handle_fault(&address, &error_code) {
+		if is_user_mode(stack_frame) {
+			return signal::on_user_fault(stack_frame, signal::SIGSEGV);
+		}
 		panic!("\nPage Fault: {:#?}\n{:#?}", error_code, stack_frame);
 	}
 }
 
 pub extern "x86-interrupt" fn general_fault_handler(stack_frame: &mut ExceptionStackFrame, _error_code: u64) {
+	if is_user_mode(stack_frame) {
+		let _context = super::functions::enter_interrupt();
+		return signal::on_user_fault(stack_frame, signal::SIGSEGV);
+	}
 	panic!("\nGeneral Protection Fault: {:#?}", stack_frame);
 }
 
 pub extern "x86-interrupt" fn invalid_opcode_handler(stack_frame: &mut ExceptionStackFrame) {
+	if is_user_mode(stack_frame) {
+		let _context = super::functions::enter_interrupt();
+		return signal::on_user_fault(stack_frame, signal::SIGILL);
+	}
 	panic!("\nInvalid Opcode Fault: {:#?}", stack_frame);
 }
 
+pub extern "x86-interrupt" fn device_not_available_handler(_stack_frame: &mut ExceptionStackFrame) {
+	// The floating point registers are switched on first use
+	// See task/extended_state
+	let _context = super::functions::enter_interrupt();
+	::task::functions::with_active_thread(|thread| {
+		::task::extended_state::on_device_not_available(thread.id, &mut thread.extended_state);
+	});
+}
+
 pub extern "x86-interrupt" fn keyboard_handler(_stack_frame: &mut ExceptionStackFrame) {
-	// Note: This handler isn't actually used because we've
-	// disabled the keyboard interrupt. See interrupts::pic_functions
-	if let Some(key_code) = ::keyboard::SYSTEM_KEYBOARD.lock().parse_port_input() {
-		::shell::SYSTEM_SHELL.lock().on_key_press(key_code);
-	}
+	// The key event is handled by the shell thread
+	// rather than in the interrupt handler
+	let _context = super::functions::enter_interrupt();
+	::keyboard::functions::on_keyboard_interrupt();
 	send_interrupt_end(false);
 }
 
-pub extern "x86-interrupt" fn timer_handler(_stack_frame: &mut ExceptionStackFrame) {
-	const TASK_SWITCH_STACK_TOP: usize = ::paging::reserved::TASK_SWITCH_STACK_TOP.raw();
-	// All the registers are pushed here
-	unsafe {
-		// First, we save the current stack pointer so we can store it in the scheduler later.
-		// We also switch stacks because if we don't, when we change page tables
-		// the stack will not longer be valid, which causes a loop of page faults.
-		// The first argument to a function is stored in the rdi register
-		asm!("mov rdi, rsp
+// Pushes the registers of the interrupted thread in the same order as
+// system_call_handler, so the saved stack of a thread that was interrupted
+// in user mode is a SystemCallFrame (see task/signal). Then switches to
+// the context switch stack and calls the function with the stack pointer
+// of the interrupted thread. The function returns the stack pointer of
+// the thread to resume, whose registers are popped before the iret.
+macro_rules! switch_context {
+	($function:path) => {{
+		const TASK_SWITCH_STACK_TOP: usize = ::paging::reserved::TASK_SWITCH_STACK_TOP.raw();
+		asm!("push rax
+			  push rbx
+			  push rcx
+			  push rdx
+			  push rsi
+			  push rdi
+			  push rbp
+			  push r8
+			  push r9
+			  push r10
+			  push r11
+			  push r12
+			  push r13
+			  push r14
+			  push r15
+			  mov rdi, rsp
 			  mov rsp, $0
+			  cld
 			  call $1
-			  mov rsp, rax"
+			  mov rsp, rax
+			  pop r15
+			  pop r14
+			  pop r13
+			  pop r12
+			  pop r11
+			  pop r10
+			  pop r9
+			  pop r8
+			  pop rbp
+			  pop rdi
+			  pop rsi
+			  pop rdx
+			  pop rcx
+			  pop rbx
+			  pop rax
+			  iretq"
 			  :: "i"(TASK_SWITCH_STACK_TOP), // i indicates that the argument is a constant
-			  "i"(::task::functions::context_switch as extern "C" fn(usize) -> usize)
-			  : "rax","rbx","rcx","rdx","rbp","rsi","rdi","r8","r9","r10","r11","r12","r13","r14","r15"
-			  : "intel");
-		// All registers are clobbered to force LLVM to push and pop all the registers
-		// onto the stack, thus saving them.
-		// If the user mode threads use floating operations too, then
-		// the floating point registers need to be pushed and popped as well
-		//
-		// Note: When the context switch happens, all the registers are still on the stack
-		// and are popped when the context switch comes back to this stack
-	}
-	// All the registers are popped here
+			  "i"($function as extern "C" fn(usize) -> usize)
+			  :: "intel", "volatile");
+	}};
 }
 
-pub extern "x86-interrupt" fn system_call_handler(_stack_frame: &mut ExceptionStackFrame) {
-	let code;
-	// TODO create real system call handler
-	unsafe {
-		// The number in r15 is moved into the `code` variable
-		asm!("mov rbx, r15" : "={rbx}"(code) :: "rbx", "r15" : "intel");
-	}
-	::system_call::functions::system_call_hook(code);
+/// Entry point of the timer interrupt
+///
+/// # Safety
+///
+/// Must only be invoked by the processor through the interrupt descriptor table
+#[naked]
+pub unsafe extern "C" fn timer_handler() {
+	// We switch stacks because if we don't, when we change page tables
+	// the stack will not longer be valid, which causes a loop of page faults.
+	// The first argument to a function is stored in the rdi register
+	//
+	// The floating point registers are saved lazily instead
+	// See task/extended_state
+	//
+	// Note: When the context switch happens, all the registers are still on the stack
+	// and are popped when the context switch comes back to this stack
+	switch_context!(::task::functions::context_switch);
+}
+
+/// Entry point of the yield interrupt
+///
+/// # Safety
+///
+/// Must only be invoked by the processor through the interrupt descriptor table
+#[naked]
+pub unsafe extern "C" fn yield_handler() {
+	// A thread that yields may be resumed by the timer handler and the
+	// other way around, so this must save registers in the same way
+	switch_context!(::task::functions::yield_switch);
+}
+
+/// Entry point of the int 0xaa system call gate
+///
+/// # Safety
+///
+/// Must only be invoked by the processor through the interrupt descriptor table
+#[naked]
+pub unsafe extern "C" fn system_call_handler() {
+	// The general purpose registers are pushed so that they form a
+	// SystemCallFrame on the stack, directly below the exception stack
+	// frame. The dispatcher reads the system call number and arguments
+	// from the frame and writes the result into the saved rax, which
+	// is then popped back into the registers of the calling thread.
+	//
+	// Note: The processor aligns the stack before pushing the exception
+	// stack frame. Five words for the exception stack frame and fifteen
+	// for the registers keeps the stack aligned for the call.
+	//
+	// The gate does not clear the direction flag, which the
+	// kernel expects to be clear, so it is cleared before the call
+	asm!("push rax
+		  push rbx
+		  push rcx
+		  push rdx
+		  push rsi
+		  push rdi
+		  push rbp
+		  push r8
+		  push r9
+		  push r10
+		  push r11
+		  push r12
+		  push r13
+		  push r14
+		  push r15
+		  mov rdi, rsp
+		  cld
+		  call $0
+		  pop r15
+		  pop r14
+		  pop r13
+		  pop r12
+		  pop r11
+		  pop r10
+		  pop r9
+		  pop r8
+		  pop rbp
+		  pop rdi
+		  pop rsi
+		  pop rdx
+		  pop rcx
+		  pop rbx
+		  pop rax
+		  iretq"
+		  :: "i"(::system_call::functions::dispatch as extern "C" fn(&mut ::system_call::SystemCallFrame))
+		  :: "intel", "volatile");
+}
+
+/// Entry point of the syscall instruction
+///
+/// # Safety
+///
+/// Must only be invoked by the processor through the IA32_LSTAR register
+#[naked]
+pub unsafe extern "C" fn fast_system_call_handler() {
+	// The syscall instruction stores the instruction pointer in rcx
+	// and the flags in r11 but leaves the stack pointer untouched.
+	// The swapgs instruction gives us access to the FastCallState
+	// (see system_call/functions) so we can switch to the kernel stack
+	// of the active thread. We then build the same exception stack
+	// frame that an int 0xaa would create, so both entries share
+	// the SystemCallFrame layout and the dispatcher.
+	//
+	// Interrupts are masked by the processor until the frame is built,
+	// and the original gs base is restored before they are enabled
+	// again so a context switch never leaks the kernel gs base.
+	//
+	// The sysret instruction returns to the instruction pointer in rcx
+	// and restores the flags from r11, which are loaded from the frame.
+	asm!("swapgs
+		  mov qword ptr gs:[0], rsp
+		  mov rsp, qword ptr gs:[8]
+		  push qword ptr gs:[24]
+		  push qword ptr gs:[0]
+		  push r11
+		  push qword ptr gs:[16]
+		  push rcx
+		  swapgs
+		  sti
+		  push rax
+		  push rbx
+		  push rcx
+		  push rdx
+		  push rsi
+		  push rdi
+		  push rbp
+		  push r8
+		  push r9
+		  push r10
+		  push r11
+		  push r12
+		  push r13
+		  push r14
+		  push r15
+		  mov rdi, rsp
+		  call $0
+		  cli
+		  pop r15
+		  pop r14
+		  pop r13
+		  pop r12
+		  pop r11
+		  pop r10
+		  pop r9
+		  pop r8
+		  pop rbp
+		  pop rdi
+		  pop rsi
+		  pop rdx
+		  pop rcx
+		  pop rbx
+		  pop rax
+		  pop rcx
+		  add rsp, 8
+		  pop r11
+		  pop rsp
+		  sysretq"
+		  :: "i"(::system_call::functions::dispatch as extern "C" fn(&mut ::system_call::SystemCallFrame))
+		  :: "intel", "volatile");
+}
+
+/// Pops a SystemCallFrame from the stack and returns to user mode
+///
+/// Threads created by fork start here. See system_call/calls/process::fork
+///
+/// # Safety
+///
+/// The stack pointer must point to a SystemCallFrame
+#[naked]
+pub unsafe extern "C" fn return_to_user() {
+	asm!("pop r15
+		  pop r14
+		  pop r13
+		  pop r12
+		  pop r11
+		  pop r10
+		  pop r9
+		  pop r8
+		  pop rbp
+		  pop rdi
+		  pop rsi
+		  pop rdx
+		  pop rcx
+		  pop rbx
+		  pop rax
+		  iretq"
+		  :::: "intel", "volatile");
+}
+
+/// Delivers the pending signals of a thread before it returns to user mode
+///
+/// Threads that were interrupted in user mode start here when they are
+/// resumed with a signal pending. See task/signal::redirect
+///
+/// # Safety
+///
+/// The stack pointer must point to a SystemCallFrame
+#[naked]
+pub unsafe extern "C" fn signal_entry() {
+	asm!("mov rdi, rsp
+		  call $0
+		  pop r15
+		  pop r14
+		  pop r13
+		  pop r12
+		  pop r11
+		  pop r10
+		  pop r9
+		  pop r8
+		  pop rbp
+		  pop rdi
+		  pop rsi
+		  pop rdx
+		  pop rcx
+		  pop rbx
+		  pop rax
+		  iretq"
+		  :: "i"(::task::signal::on_signal_entry as extern "C" fn(&mut ::system_call::SystemCallFrame))
+		  :: "intel", "volatile");
+}
+
+/// Raises the signal for a fault that occurred in user mode
+///
+/// The fault handler returns here in kernel mode with the registers
+/// of the thread, at the top of its kernel stack. See task/signal::on_user_fault
+///
+/// # Safety
+///
+/// Must only be returned to by a fault handler
+#[naked]
+pub unsafe extern "C" fn user_fault_entry() {
+	// Room is left for the exception stack frame, which
+	// is filled in from the saved fault, and the registers
+	// are pushed below it to form a SystemCallFrame
+	asm!("sub rsp, 40
+		  push rax
+		  push rbx
+		  push rcx
+		  push rdx
+		  push rsi
+		  push rdi
+		  push rbp
+		  push r8
+		  push r9
+		  push r10
+		  push r11
+		  push r12
+		  push r13
+		  push r14
+		  push r15
+		  mov rdi, rsp
+		  cld
+		  call $0
+		  pop r15
+		  pop r14
+		  pop r13
+		  pop r12
+		  pop r11
+		  pop r10
+		  pop r9
+		  pop r8
+		  pop rbp
+		  pop rdi
+		  pop rsi
+		  pop rdx
+		  pop rcx
+		  pop rbx
+		  pop rax
+		  iretq"
+		  :: "i"(::task::signal::on_fault_entry as extern "C" fn(&mut ::system_call::SystemCallFrame))
+		  :: "intel", "volatile");
 }
@@ -1,24 +1,133 @@
-const BASE_FREQUENCY: u32 = 1_193_180;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use utility::BootOptions;
+use x86_64::instructions::port::inb;
+use x86_64::instructions::port::outb;
+
+const BASE_FREQUENCY: u32 = 1_193_182;
 const COMMAND_PORT: u16 = 0x43;
 const DATA_PORT: u16 = 0x40;
 
+// Channel 0, low byte then high byte, binary counting
+const PERIODIC_MODE: u8 = 0b0011_0100;
+const ONE_SHOT_MODE: u8 = 0b0011_0000;
+const LATCH_COMMAND: u8 = 0b0000_0000;
+const MAX_COUNT: usize = 0xffff;
+
+// The number of timer interrupts per second, unless
+// the timer_frequency boot option is given
+pub const DEFAULT_FREQUENCY: u32 = 100;
+
+// The timer either interrupts once every tick or, when only the idle
+// thread can run, once after several ticks. See set_tick_count
+static DIVISOR: AtomicUsize = AtomicUsize::new(0);
+static TICK_COUNT: AtomicUsize = AtomicUsize::new(1);
+
+// The monotonic clock is kept in timer counts, which are added
+// whenever the timer interrupts or is programmed again
+static ELAPSED_COUNTS: AtomicUsize = AtomicUsize::new(0);
+static PROGRAMMED_COUNT: AtomicUsize = AtomicUsize::new(0);
+static EXPIRED: AtomicBool = AtomicBool::new(false);
+static LAST_NANOSECONDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the timer frequency from the boot options
+///
+/// The frequency is needed before the timer is started
+/// to create the scheduler. See task/schedulers
+pub fn configure(options: &BootOptions) {
+	// The divisor must fit in sixteen bits
+	let minimum_frequency = BASE_FREQUENCY / MAX_COUNT as u32 + 1;
+	let frequency = options.get("timer_frequency")
+	                       .and_then(|frequency| frequency.parse::<u32>().ok())
+	                       .unwrap_or(DEFAULT_FREQUENCY)
+	                       .max(minimum_frequency)
+	                       .min(BASE_FREQUENCY);
+
+	DIVISOR.store((BASE_FREQUENCY / frequency) as usize, Ordering::SeqCst);
+}
+
 pub fn initialize() {
-	const DIVIDER_FREQUENCY: u32 = 100;
+	unsafe { program(PERIODIC_MODE, divisor()); }
+}
+
+/// The number of timer ticks per second
+pub fn frequency() -> u32 {
+	BASE_FREQUENCY / divisor() as u32
+}
+
+fn divisor() -> usize {
+	DIVISOR.load(Ordering::SeqCst)
+}
+
+/// Called by the timer interrupt handler
+///
+/// Returns the number of ticks since the last interrupt
+pub fn on_interrupt() -> usize {
+	// A one shot timer keeps counting down from its
+	// maximum value after it interrupts
+	let tick_count = TICK_COUNT.load(Ordering::SeqCst);
+	ELAPSED_COUNTS.fetch_add(PROGRAMMED_COUNT.load(Ordering::SeqCst), Ordering::SeqCst);
+	EXPIRED.store(tick_count > 1, Ordering::SeqCst);
+	tick_count
+}
+
+/// Programs the timer to interrupt after the number of ticks
+///
+/// A tick count of one interrupts on every tick. The tick count
+/// is limited by the largest count that the timer supports
+pub fn set_tick_count(tick_count: usize) {
+	let tick_count = tick_count.min(MAX_COUNT / divisor()).max(1);
+	if tick_count == 1 && TICK_COUNT.load(Ordering::SeqCst) == 1 {
+		return;
+	}
+
+	// The time since the last interrupt would be lost otherwise
 	unsafe {
-		set_frequency(DIVIDER_FREQUENCY);
+		ELAPSED_COUNTS.fetch_add(current_elapsed(), Ordering::SeqCst);
+		TICK_COUNT.store(tick_count, Ordering::SeqCst);
+		match tick_count {
+			1 => program(PERIODIC_MODE, divisor()),
+			_ => program(ONE_SHOT_MODE, divisor() * tick_count),
+		}
 	}
 }
 
-unsafe fn set_frequency(hertz: u32) {
-	use x86_64::instructions::port::outb;
+/// The nanoseconds since the timer was initialized
+///
+/// This must be called with interrupts disabled
+pub fn nanoseconds() -> u64 {
+	let counts = ELAPSED_COUNTS.load(Ordering::SeqCst) + unsafe { current_elapsed() };
 
-	let division = BASE_FREQUENCY / hertz;
-	let mode = 0b0011_0110;
+	// The counts are split to avoid overflow
+	let seconds = counts / BASE_FREQUENCY as usize;
+	let remainder = counts % BASE_FREQUENCY as usize;
+	let nanoseconds = seconds * 1_000_000_000 + remainder * 1_000_000_000 / BASE_FREQUENCY as usize;
+
+	// The timer can reload before its interrupt is handled, which
+	// would otherwise make the clock appear to go backwards
+	let last = LAST_NANOSECONDS.load(Ordering::SeqCst).max(nanoseconds);
+	LAST_NANOSECONDS.store(last, Ordering::SeqCst);
+	last as u64
+}
+
+/// The counts since the timer last interrupted or was programmed
+unsafe fn current_elapsed() -> usize {
+	if EXPIRED.load(Ordering::SeqCst) {
+		return 0;
+	}
+
+	outb(COMMAND_PORT, LATCH_COMMAND);
+	let low = inb(DATA_PORT) as usize;
+	let high = inb(DATA_PORT) as usize;
+	PROGRAMMED_COUNT.load(Ordering::SeqCst).saturating_sub((high << 8) | low)
+}
 
-	let low = division & 0x0000_00ff;
-	let high = (division & 0x0000_ff00) >> 8;
+unsafe fn program(mode: u8, count: usize) {
+	PROGRAMMED_COUNT.store(count, Ordering::SeqCst);
+	EXPIRED.store(false, Ordering::SeqCst);
 
 	outb(COMMAND_PORT, mode);
-	outb(DATA_PORT, low as u8);
-	outb(DATA_PORT, high as u8);
+	outb(DATA_PORT, (count & 0xff) as u8);
+	outb(DATA_PORT, ((count >> 8) & 0xff) as u8);
 }
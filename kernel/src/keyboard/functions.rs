@@ -1,4 +1,49 @@
-use ::spin::Mutex;
+use alloc::VecDeque;
 use super::drivers::PS2Driver;
+use super::KeyEvent;
+use task::WaitQueue;
+use utility::Global;
 
-pub static SYSTEM_KEYBOARD: Mutex<PS2Driver> = Mutex::new(PS2Driver::new());
\ No newline at end of file
+pub static SYSTEM_KEYBOARD: Global<PS2Driver> = Global::new_irq_safe("SYSTEM_KEYBOARD");
+
+// Key events are parsed by the interrupt handler and
+// handled later by a kernel thread. See shell/functions
+const KEY_EVENT_CAPACITY: usize = 64;
+static KEY_EVENTS: Global<VecDeque<KeyEvent>> = Global::new_irq_safe("KEY_EVENTS");
+static KEY_EVENT_QUEUE: WaitQueue = WaitQueue::new();
+
+pub fn initialize() {
+	SYSTEM_KEYBOARD.set(PS2Driver::new());
+	KEY_EVENTS.set(VecDeque::new());
+}
+
+/// Reads a key event from the keyboard and wakes a waiting thread
+///
+/// This must only be called from the keyboard interrupt handler
+pub fn on_keyboard_interrupt() {
+	let key_event = SYSTEM_KEYBOARD.lock().parse_port_input();
+	if let Some(key_event) = key_event {
+		// Events are dropped while the buffer is full
+		let mut key_events = KEY_EVENTS.lock();
+		if key_events.len() < KEY_EVENT_CAPACITY {
+			key_events.push_back(key_event);
+		}
+	}
+	KEY_EVENT_QUEUE.wake_one();
+}
+
+/// Returns the next key event without blocking
+pub fn poll_key_event() -> Option<KeyEvent> {
+	KEY_EVENTS.lock().pop_front()
+}
+
+/// Blocks until a key event is available
+pub fn next_key_event() -> KeyEvent {
+	loop {
+		if let Some(key_event) = poll_key_event() {
+			return key_event;
+		}
+
+		KEY_EVENT_QUEUE.wait_until(|| !KEY_EVENTS.lock().is_empty());
+	}
+}
@@ -4,6 +4,7 @@
 #![feature(abi_x86_interrupt)]
 #![feature(alloc)]
 #![feature(box_syntax)]
+#![feature(naked_functions)]
 #![no_std]
 
 #[macro_use]
@@ -67,11 +68,13 @@ pub extern "C" fn boot_entry(boot_information: usize) -> ! {
 
 	// Prepare the scheduler for when interrupts are enabled
 	// See task/mod.rs for loading a user mode program
+	let boot_options = ::utility::BootOptions::from_boot_information(&boot_information);
+	::interrupts::pit_functions::configure(&boot_options);
 	::task::functions::pre_initialize();
-	::task::functions::initialize();
+	::task::functions::initialize(base_table, &boot_options);
 
-	// Note: not actually used because the keyboard interrupt
-	// is disabled
+	// The shell runs on a kernel thread once interrupts are enabled
+	::keyboard::functions::initialize();
 	::shell::functions::initialize();
 
 	// Enables interrupts, especially the timer interrupt
@@ -0,0 +1,46 @@
+use alloc::BTreeMap;
+use super::Frame;
+use super::FrameLike;
+
+// Copy on write pages map the same frame into several page tables
+// A frame that is mapped once is not stored, so only shared frames
+// take up memory here
+
+pub struct FrameReferences {
+	counts: BTreeMap<usize, usize>,
+}
+
+impl FrameReferences {
+	pub fn new() -> FrameReferences {
+		FrameReferences {
+			counts: BTreeMap::new(),
+		}
+	}
+
+	/// Returns the number of page tables that map the frame
+	pub fn count(&self, frame: &Frame) -> usize {
+		self.counts.get(&frame.index()).cloned().unwrap_or(1)
+	}
+
+	/// Adds a reference to a frame that is mapped in another table
+	pub fn share(&mut self, frame: &Frame) {
+		let count = self.count(frame);
+		self.counts.insert(frame.index(), count + 1);
+	}
+
+	/// Removes a reference to the frame and returns
+	/// true if it was the last reference
+	pub fn release(&mut self, frame: &Frame) -> bool {
+		match self.count(frame) {
+			1 => true,
+			2 => {
+				self.counts.remove(&frame.index());
+				false
+			}
+			count => {
+				self.counts.insert(frame.index(), count - 1);
+				false
+			}
+		}
+	}
+}
@@ -5,6 +5,7 @@ use paging::EntryFlags;
 use paging::PageLike;
 use paging::reserved::HEAP_BOTTOM;
 use paging::VirtualAddress;
+use super::frame_references::FrameReferences;
 use super::generic_allocators::BootAllocator;
 use super::generic_allocators::GlobalFrameAllocator;
 use utility::Global;
@@ -12,7 +13,10 @@ use x86_64::structures::idt::PageFaultErrorCode;
 
 #[cfg_attr(not(test), global_allocator)]
 pub static HEAP_ALLOCATOR: LockedHeap = LockedHeap::empty();
-pub static FRAME_ALLOCATOR: Global<GlobalFrameAllocator> = Global::new("FRAME_ALLOCATOR");
+pub static FRAME_ALLOCATOR: Global<GlobalFrameAllocator> = Global::new_irq_safe("FRAME_ALLOCATOR");
+
+// This is also locked when handling a copy on write fault
+pub static FRAME_REFERENCES: Global<FrameReferences> = Global::new_irq_safe("FRAME_REFERENCES");
 
 pub fn initialize(boot_structure: ::utility::MultibootStructure) -> BootAllocator {
 	let _status = ::display::text_mode::BootStatus::new("Creating boot frame allocator");
@@ -24,6 +28,7 @@ pub fn post_paging_initialize(mut allocator: BootAllocator) {
 	create_initial_heap(&mut allocator);
 	unsafe { HEAP_ALLOCATOR.lock().init(HEAP_BOTTOM.raw(), ::paging::reserved::HEAP_SIZE); }
 	FRAME_ALLOCATOR.set(GlobalFrameAllocator::Boot(allocator));
+	FRAME_REFERENCES.set(FrameReferences::new());
 }
 
 pub fn post_initialize(boot_information: &::multiboot2::BootInformation) {
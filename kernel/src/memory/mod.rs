@@ -5,6 +5,7 @@ pub use self::frame::FrameLike;
 pub use self::frame::HugeFrame;
 pub use self::frame_allocator::FrameLikeAllocator;
 pub use self::functions::FRAME_ALLOCATOR;
+pub use self::functions::FRAME_REFERENCES;
 pub use self::generic_allocators::GenericAllocator;
 pub use self::memory_area::MemoryArea;
 
@@ -16,3 +17,4 @@ pub mod address;
 pub mod memory_area;
 pub mod generic_allocators;
 pub mod functions;
+pub mod frame_references;
@@ -0,0 +1,190 @@
+use core::ops::DerefMut;
+use interrupts::functions::without_interrupts;
+use memory::Frame;
+use memory::FRAME_ALLOCATOR;
+use memory::FRAME_REFERENCES;
+use memory::FrameLike;
+use memory::FrameLikeAllocator;
+use super::ACTIVE_PAGE_TABLE;
+use super::EntryFlags;
+use super::InactivePageTable;
+use super::Page;
+use super::PageEntry;
+use super::PageLike;
+use super::PageMapper;
+use super::PageTable;
+use super::table_level::Level1;
+use super::VirtualAddress;
+use x86_64::structures::idt::PageFaultErrorCode;
+
+// A forked address space shares every user frame with the original.
+// Writable pages are marked read only and COPY_ON_WRITE in both tables
+// so the first write to one of them causes a page fault. The fault is
+// resolved by giving the table its own copy of the frame, unless no
+// other table references the frame anymore.
+
+const ENTRY_COUNT: usize = PageTable::<Level1>::ENTRY_COUNT;
+const USER_ENTRY_COUNT: usize = ENTRY_COUNT / 2;
+
+// The frame references are updated on the heap, which may need to lock
+// the active table to grow, so pages are shared in batches
+const BATCH_SIZE: usize = 64;
+
+#[derive(Clone, Copy)]
+struct SharedPage {
+	page: usize,
+	frame: usize,
+	flags: EntryFlags,
+}
+
+/// Maps the user accessible pages of the active table into the child
+/// table and marks the writable pages as copy on write in both tables
+pub fn share_user_space(child: &mut InactivePageTable) {
+	let empty = SharedPage { page: 0, frame: 0, flags: EntryFlags::empty() };
+	let mut next_page = 0;
+	loop {
+		let mut batch = [empty; BATCH_SIZE];
+		let mut count = 0;
+		without_interrupts(|| {
+			{
+				let mut table = ACTIVE_PAGE_TABLE.lock();
+				while count < BATCH_SIZE {
+					let page_index = match next_mapped_page(&table, next_page) {
+						Some(page_index) => page_index,
+						None => break,
+					};
+					next_page = page_index + 1;
+
+					// The kernel stack of the thread is not shared
+					let entry = leaf_entry_mut(&mut table, &Page::from_index(page_index)).unwrap();
+					let mut flags = entry.flags();
+					if !flags.contains(EntryFlags::USER_ACCESSIBLE) {
+						continue;
+					}
+
+					let frame: Frame = entry.frame().unwrap();
+					if flags.contains(EntryFlags::WRITABLE) {
+						flags = (flags - EntryFlags::WRITABLE) | EntryFlags::COPY_ON_WRITE;
+						entry.set(frame.clone(), flags);
+					}
+
+					batch[count] = SharedPage { page: page_index, frame: frame.index(), flags };
+					count += 1;
+				}
+				::x86_64::instructions::tlb::flush_all();
+			}
+
+			let mut references = FRAME_REFERENCES.lock();
+			for shared in &batch[..count] {
+				references.share(&Frame::from_index(shared.frame));
+			}
+		});
+
+		without_interrupts(|| {
+			let mut allocator = FRAME_ALLOCATOR.lock();
+			ACTIVE_PAGE_TABLE.lock().with(child, allocator.deref_mut(), |mapper, allocator| {
+				for shared in &batch[..count] {
+					let page = Page::from_index(shared.page);
+					mapper.map_to(page, Frame::from_index(shared.frame), shared.flags, allocator);
+				}
+			});
+		});
+
+		if count < BATCH_SIZE {
+			break;
+		}
+	}
+}
+
+/// Resolves a write to a copy on write page and returns
+/// false if the page fault was caused by something else
+pub fn handle_fault(address: &VirtualAddress, error_code: &PageFaultErrorCode) -> bool {
+	let write_violation = PageFaultErrorCode::PROTECTION_VIOLATION | PageFaultErrorCode::CAUSED_BY_WRITE;
+	if !error_code.contains(write_violation) || *address > super::reserved::USER_SPACE_TOP {
+		return false;
+	}
+
+	let page = Page::from_address(address.clone());
+	let mut references = FRAME_REFERENCES.lock();
+	let mut allocator = FRAME_ALLOCATOR.lock();
+	let mut table = ACTIVE_PAGE_TABLE.lock();
+
+	let (frame, flags) = match leaf_entry_mut(&mut table, &page) {
+		Some(entry) => match entry.frame::<Frame>() {
+			Some(frame) => (frame, entry.flags()),
+			None => return false,
+		},
+		None => return false,
+	};
+
+	if !flags.contains(EntryFlags::COPY_ON_WRITE) {
+		return false;
+	}
+
+	// The last table to reference a frame can keep it
+	let flags = (flags - EntryFlags::COPY_ON_WRITE) | EntryFlags::WRITABLE;
+	let frame = match references.count(&frame) {
+		1 => frame,
+		_ => {
+			references.release(&frame);
+			let copy = allocator.allocate().expect("Out of memory: COPY_ON_WRITE");
+			let temporary_page = Page::from_address(super::reserved::COPY_ON_WRITE_TEMPORARY_PAGE);
+			table.map_to(temporary_page.clone(), copy.clone(), EntryFlags::WRITABLE, allocator.deref_mut());
+			unsafe {
+				::core::ptr::copy_nonoverlapping(page.start_address().raw() as *const u8,
+				                                 temporary_page.start_address().raw() as *mut u8,
+				                                 Page::SIZE as usize);
+			}
+			table.discard(temporary_page, allocator.deref_mut());
+			copy
+		}
+	};
+
+	leaf_entry_mut(&mut table, &page).unwrap().set(frame, flags);
+	table.flush_table_entry(&page);
+	true
+}
+
+fn leaf_entry_mut<'a>(mapper: &'a mut PageMapper, page: &Page) -> Option<&'a mut PageEntry> {
+	mapper.table_mut().next_table_mut(page.table_4_index())
+	      .and_then(|table_3| table_3.next_table_mut(page.table_3_index()))
+	      .and_then(|table_2| table_2.next_table_mut(page.table_2_index()))
+	      .map(|table_1| &mut table_1[page.table_1_index()])
+}
+
+/// Returns the index of the first mapped user page at or after the index
+fn next_mapped_page(mapper: &PageMapper, start: usize) -> Option<usize> {
+	// Entries that only map pages before the start are skipped
+	let table_4 = mapper.table();
+	for index_4 in 0..USER_ENTRY_COUNT {
+		let base_4 = index_4 << 27;
+		let table_3 = match table_4.next_table(index_4) {
+			Some(table_3) if base_4 + (1 << 27) > start => table_3,
+			_ => continue,
+		};
+
+		for index_3 in 0..ENTRY_COUNT {
+			let base_3 = base_4 | (index_3 << 18);
+			let table_2 = match table_3.next_table(index_3) {
+				Some(table_2) if base_3 + (1 << 18) > start => table_2,
+				_ => continue,
+			};
+
+			for index_2 in 0..ENTRY_COUNT {
+				let base_2 = base_3 | (index_2 << 9);
+				let table_1 = match table_2.next_table(index_2) {
+					Some(table_1) if base_2 + (1 << 9) > start => table_1,
+					_ => continue,
+				};
+
+				for index_1 in 0..ENTRY_COUNT {
+					let page_index = base_2 | index_1;
+					if page_index >= start && table_1[index_1].flags().contains(EntryFlags::PRESENT) {
+						return Some(page_index);
+					}
+				}
+			}
+		}
+	}
+	None
+}
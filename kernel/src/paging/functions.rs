@@ -13,7 +13,7 @@ use super::PageMapper;
 use super::VirtualAddress;
 use utility::Global;
 
-pub static ACTIVE_PAGE_TABLE: Global<ActivePageTable> = Global::new("ACTIVE_PAGE_TABLE");
+pub static ACTIVE_PAGE_TABLE: Global<ActivePageTable> = Global::new_irq_safe("ACTIVE_PAGE_TABLE");
 
 pub fn initialize<A>(boot_information: &BootInformation, allocator: &mut A) -> InactivePageTable
 	where A: FrameLikeAllocator<Frame> {
@@ -25,4 +25,7 @@ pub mod temporary_page;
 pub mod page_mapper;
 pub mod functions;
 pub mod reserved;
-pub mod page_iter;
\ No newline at end of file
+pub mod page_iter;
+pub mod user_access;
+pub mod teardown;
+pub mod copy_on_write;
\ No newline at end of file
@@ -54,6 +54,8 @@ bitflags! {
         const DIRTY =           1 << 6;
         const HUGE_PAGE =       1 << 7;
         const GLOBAL =          1 << 8;
+        // Ignored by the processor, see paging/copy_on_write
+        const COPY_ON_WRITE =   1 << 9;
         const NO_EXECUTE =      1 << 63;
     }
 }
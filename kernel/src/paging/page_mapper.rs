@@ -72,6 +72,51 @@ impl PageMapper {
 		Some(PhysicalAddress::new(raw_address))
 	}
 
+	/// Returns the flags that apply to the address after combining the
+	/// entries of every table level, along with the size of the page
+	/// that contains the address
+	///
+	/// A page is only writable or user accessible if every table
+	/// entry used to translate it is writable or user accessible
+	pub fn effective_flags(&self, address: &VirtualAddress) -> Option<(EntryFlags, u64)> {
+		use super::HugePage;
+		let page = Page::from_address(address.clone());
+		let combine = |flags: EntryFlags, entry: EntryFlags| -> Option<EntryFlags> {
+			if !entry.contains(EntryFlags::PRESENT) {
+				return None;
+			}
+			let permissions = flags & entry & (EntryFlags::WRITABLE | EntryFlags::USER_ACCESSIBLE);
+			let inherited = (flags | entry) & (EntryFlags::NO_EXECUTE | EntryFlags::COPY_ON_WRITE);
+			Some(permissions | EntryFlags::PRESENT | inherited)
+		};
+
+		let flags = EntryFlags::PRESENT | EntryFlags::WRITABLE | EntryFlags::USER_ACCESSIBLE;
+		let table_4 = self.table();
+		let flags = combine(flags, table_4[page.table_4_index()].flags())?;
+		let table_3 = table_4.next_table(page.table_4_index())?;
+
+		// The kernel never maps 1 GiB pages, but the whole
+		// page is covered by the entry if one is found
+		const GIGABYTE_PAGE_SIZE: u64 = 512 * HugePage::SIZE;
+		let entry_flags = table_3[page.table_3_index()].flags();
+		let flags = combine(flags, entry_flags)?;
+		if entry_flags.contains(EntryFlags::HUGE_PAGE) {
+			return Some((flags, GIGABYTE_PAGE_SIZE));
+		}
+
+		let table_2 = table_3.next_table(page.table_3_index())?;
+
+		let entry_flags = table_2[page.table_2_index()].flags();
+		let flags = combine(flags, entry_flags)?;
+		if entry_flags.contains(EntryFlags::HUGE_PAGE) {
+			return Some((flags, HugePage::SIZE));
+		}
+
+		let table_1 = table_2.next_table(page.table_2_index())?;
+		let flags = combine(flags, table_1[page.table_1_index()].flags())?;
+		Some((flags, Page::SIZE))
+	}
+
 	pub fn flush_table_entry<P>(&mut self, page: &P) where P: PageLike {
 		use x86_64::VirtualAddress;
 		::x86_64::instructions::tlb::flush(VirtualAddress(page.start_address().raw()));
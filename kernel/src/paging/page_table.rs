@@ -13,6 +13,12 @@ pub struct PageTable<L: TableLevel> {
 }
 
 impl<L> PageTable<L> where L: TableLevel {
+	pub const ENTRY_COUNT: usize = ENTRY_COUNT;
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.iter().all(|entry| entry.is_unused())
+	}
+
 	pub fn clear(&mut self) {
 		for entry in self.entries.iter_mut() {
 			entry.set_unused();
@@ -5,12 +5,27 @@ use super::VirtualAddress;
 // This makes it easy to see which areas of memory are already used for
 // some function
 
+// User mode programs may only be loaded into the lower half
+// of the virtual address space
+pub const USER_SPACE_TOP: VirtualAddress = VirtualAddress::new(0x0000_7fff_ffff_ffff);
+
+// Every thread has its stacks at the top of user space, so the stack
+// does not move when exec replaces the program. See task/loaders/stack
+pub const USER_STACK_SIZE: usize = 16 * super::Page::SIZE as usize;
+pub const USER_STACK_BOTTOM: VirtualAddress = VirtualAddress::new(USER_SPACE_TOP.raw() + 1 - USER_STACK_SIZE);
+
+// The thread local storage block and thread control block are mapped
+// just below the stack. See task/loaders/tls
+pub const USER_TLS_SIZE: usize = 16 * super::Page::SIZE as usize;
+pub const USER_TLS_BOTTOM: VirtualAddress = VirtualAddress::new(USER_STACK_BOTTOM.raw() - USER_TLS_SIZE);
+
 // Note well: The first sixteen bits of a virtual address must match
 // the 17th bit (from the left) due to the intel memory hole.
 // Otherwise, accessing the address will cause a General Protection Fault
 pub const TEMPORARY_PAGE: VirtualAddress = VirtualAddress::new(0xffff_f000_0000_1000);
 pub const ACTIVE_TABLE_WITH_TEMPORARY_PAGE: VirtualAddress = TEMPORARY_PAGE.offset(0x1000);
 pub const CLONE_SHALLOW_TEMPORARY_PAGE: VirtualAddress = ACTIVE_TABLE_WITH_TEMPORARY_PAGE.offset(0x1000);
+pub const COPY_ON_WRITE_TEMPORARY_PAGE: VirtualAddress = CLONE_SHALLOW_TEMPORARY_PAGE.offset(0x1000);
 pub const HUGE_TEMPORARY_PAGE: VirtualAddress = VirtualAddress::new(0xffff_f000_1000_0000);
 
 pub const HEAP_SIZE: usize = 0x0100_0000_0000 - 1;
@@ -26,3 +41,16 @@ pub const HUGE_FRAME_STORE_TOP: VirtualAddress = HUGE_FRAME_STORE_BOTTOM.offset(
 pub const TASK_SWITCH_STACK_SIZE: usize = super::Page::SIZE as usize - 1;
 pub const TASK_SWITCH_STACK_BOTTOM: VirtualAddress = HUGE_FRAME_STORE_TOP.offset(1);
 pub const TASK_SWITCH_STACK_TOP: VirtualAddress = TASK_SWITCH_STACK_BOTTOM.offset(TASK_SWITCH_STACK_SIZE);
+
+pub const IDLE_STACK_SIZE: usize = super::Page::SIZE as usize - 1;
+pub const IDLE_STACK_BOTTOM: VirtualAddress = TASK_SWITCH_STACK_TOP.offset(1);
+pub const IDLE_STACK_TOP: VirtualAddress = IDLE_STACK_BOTTOM.offset(IDLE_STACK_SIZE);
+
+// Kernel threads have their stacks in slots above the idle stack
+// The lowest page of every slot is never mapped, so a stack overflow
+// page faults instead of overwriting the stack below. See task/kernel_stack
+pub const KERNEL_STACK_SIZE: usize = 4 * super::Page::SIZE as usize;
+pub const KERNEL_STACK_SLOT_SIZE: usize = KERNEL_STACK_SIZE + super::Page::SIZE as usize;
+pub const KERNEL_STACK_SLOT_COUNT: usize = 256;
+pub const KERNEL_STACKS_BOTTOM: VirtualAddress = IDLE_STACK_TOP.offset(1);
+pub const KERNEL_STACKS_TOP: VirtualAddress = KERNEL_STACKS_BOTTOM.offset(KERNEL_STACK_SLOT_SIZE * KERNEL_STACK_SLOT_COUNT - 1);
@@ -0,0 +1,164 @@
+use core::ops::DerefMut;
+use core::ops::Range;
+use interrupts::functions::without_interrupts;
+use memory::Frame;
+use memory::FRAME_ALLOCATOR;
+use memory::FRAME_REFERENCES;
+use memory::FrameLike;
+use memory::FrameLikeAllocator;
+use super::ACTIVE_PAGE_TABLE;
+use super::InactivePageTable;
+use super::PageTable;
+use super::table_level::HierarchicalLevel;
+use super::table_level::Level1;
+use super::table_level::Level2;
+use super::table_level::Level3;
+
+// User space covers the lower half of the address space. The other
+// root table entries (the kernel directories created in paging/functions
+// and the recursive mapping) are shared between every table and must
+// never be freed
+const USER_ENTRY_COUNT: usize = 256;
+
+// Deallocating a frame may map a new page for the frame store
+// (see structures/frame_store), so frames are collected while the
+// tables are locked and returned to the allocator afterwards
+const BATCH_SIZE: usize = 64;
+
+struct FrameBatch {
+	frames: [usize; BATCH_SIZE],
+	count: usize,
+	retained: Option<usize>,
+}
+
+impl FrameBatch {
+	fn new(retained: Option<&Frame>) -> FrameBatch {
+		FrameBatch {
+			frames: [0; BATCH_SIZE],
+			count: 0,
+			retained: retained.map(|frame| frame.index()),
+		}
+	}
+
+	fn is_full(&self) -> bool {
+		self.count == BATCH_SIZE
+	}
+
+	fn push(&mut self, frame: Frame) {
+		self.frames[self.count] = frame.index();
+		self.count += 1;
+	}
+
+	fn deallocate(&self) {
+		without_interrupts(|| {
+			// Frames shared with another table are only freed by the last table
+			let mut references = FRAME_REFERENCES.lock();
+			let mut allocator = FRAME_ALLOCATOR.lock();
+			for index in &self.frames[..self.count] {
+				let frame = Frame::from_index(*index);
+				if references.release(&frame) {
+					allocator.deallocate(frame);
+				}
+			}
+		});
+	}
+}
+
+/// Frees the user space of the table and then the table itself
+///
+/// The table must not be active
+pub fn free_table(mut table: InactivePageTable) {
+	let active_address = without_interrupts(|| ACTIVE_PAGE_TABLE.lock().current_table_address());
+	assert_ne!(table.table_root().start_address().raw(), active_address.raw(), "Freeing the active page table");
+
+	free_user_space(&mut table, None);
+	let mut batch = FrameBatch::new(None);
+	batch.push(table.table_root().clone());
+	batch.deallocate();
+}
+
+/// Unmaps every page in the user half of the table and frees the
+/// mapped frames along with the page tables that are no longer needed
+///
+/// The page mapped to the retained frame is kept. This allows the
+/// active table to be cleared while running on its kernel stack
+pub fn free_user_space(table: &mut InactivePageTable, retained: Option<&Frame>) {
+	loop {
+		let mut batch = FrameBatch::new(retained);
+		without_interrupts(|| {
+			let mut allocator = FRAME_ALLOCATOR.lock();
+			ACTIVE_PAGE_TABLE.lock().with(table, allocator.deref_mut(), |mapper, _| {
+				collect_entries(mapper.table_mut(), 0..USER_ENTRY_COUNT, &mut batch, collect_table_3);
+			});
+		});
+
+		// A batch that is not full means the walk reached the end
+		let finished = !batch.is_full();
+		batch.deallocate();
+		if finished {
+			break;
+		}
+	}
+}
+
+fn collect_table_3(table: &mut PageTable<Level3>, batch: &mut FrameBatch) {
+	collect_entries(table, 0..PageTable::<Level3>::ENTRY_COUNT, batch, collect_table_2);
+}
+
+fn collect_table_2(table: &mut PageTable<Level2>, batch: &mut FrameBatch) {
+	collect_entries(table, 0..PageTable::<Level2>::ENTRY_COUNT, batch, collect_table_1);
+}
+
+fn collect_table_1(table: &mut PageTable<Level1>, batch: &mut FrameBatch) {
+	for index in 0..PageTable::<Level1>::ENTRY_COUNT {
+		if batch.is_full() {
+			return;
+		}
+
+		match table[index].frame::<Frame>() {
+			Some(ref frame) if Some(frame.index()) == batch.retained => continue,
+			Some(frame) => batch.push(frame),
+			None => (),
+		}
+		table[index].set_unused();
+	}
+}
+
+/// Collects the frames below each entry and then the frame of the
+/// next level table once it is empty
+fn collect_entries<L>(table: &mut PageTable<L>, entries: Range<usize>, batch: &mut FrameBatch,
+                      collect_next: fn(&mut PageTable<L::NextLevel>, &mut FrameBatch))
+	where L: HierarchicalLevel {
+	for index in entries {
+		if batch.is_full() {
+			return;
+		}
+
+		if table[index].is_unused() {
+			continue;
+		}
+
+		// User space is only mapped with normal pages, so there
+		// is nothing to free below an entry without a table
+		if table.next_table(index).is_none() {
+			table[index].set_unused();
+			continue;
+		}
+
+		{
+			let next_table = table.next_table_mut(index).unwrap();
+			collect_next(next_table, batch);
+			if batch.is_full() {
+				return;
+			}
+
+			// The table still maps the retained frame
+			if !next_table.is_empty() {
+				continue;
+			}
+		}
+
+		batch.push(table[index].frame().unwrap());
+		table[index].set_unused();
+	}
+}
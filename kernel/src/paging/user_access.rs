@@ -0,0 +1,155 @@
+use alloc::String;
+use alloc::Vec;
+use super::EntryFlags;
+use super::VirtualAddress;
+
+// Pointers passed in from user mode cannot be trusted. Before the kernel
+// reads or writes through one, every page in the range is checked in the
+// active page table so that a bad pointer results in an error rather
+// than a page fault inside the kernel
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum UserAccessError {
+	/// The range is not entirely inside user space
+	OutOfBounds,
+	/// A page in the range is not mapped with the required flags
+	NotAccessible,
+	/// A string is not valid UTF-8
+	InvalidString,
+	/// A string or array is longer than the allowed size
+	TooLarge,
+}
+
+pub type UserAccessResult<T> = Result<T, UserAccessError>;
+
+/// Checks that every page in the range is mapped, user accessible
+/// and, if `writable` is set, writable
+pub fn validate_range(address: &VirtualAddress, size: usize, writable: bool) -> UserAccessResult<()> {
+	// The timer handler locks the active page table to switch
+	// threads, so it must not be preempted while it is held here
+	::interrupts::functions::without_interrupts(|| {
+		let table = super::ACTIVE_PAGE_TABLE.lock();
+		check_range(address, size, writable, |address| table.effective_flags(address))
+	})
+}
+
+/// Checks the range like validate_range with the effective flags
+/// and page size of each address given by `lookup`
+pub fn check_range<F>(address: &VirtualAddress, size: usize, writable: bool, lookup: F) -> UserAccessResult<()>
+	where F: Fn(&VirtualAddress) -> Option<(EntryFlags, u64)> {
+	if size == 0 {
+		return Ok(());
+	}
+
+	let last_address = address.raw().checked_add(size - 1).ok_or(UserAccessError::OutOfBounds)?;
+	if last_address > super::reserved::USER_SPACE_TOP.raw() {
+		return Err(UserAccessError::OutOfBounds);
+	}
+
+	let mut required = EntryFlags::PRESENT | EntryFlags::USER_ACCESSIBLE;
+	if writable {
+		required |= EntryFlags::WRITABLE;
+	}
+
+	let mut current = address.raw();
+	loop {
+		let (flags, page_size) = lookup(&VirtualAddress::new(current)).ok_or(UserAccessError::NotAccessible)?;
+
+		// Copy on write pages become writable when written to
+		let mut flags = flags;
+		if flags.contains(EntryFlags::COPY_ON_WRITE) {
+			flags |= EntryFlags::WRITABLE;
+		}
+
+		if !flags.contains(required) {
+			return Err(UserAccessError::NotAccessible);
+		}
+
+		// Huge pages are skipped over entirely
+		let page_end = (current & !(page_size as usize - 1)) + (page_size as usize - 1);
+		if page_end >= last_address {
+			return Ok(());
+		}
+		current = page_end + 1;
+	}
+}
+
+/// Copies the user range starting at `source` into `destination`
+pub fn copy_from_user(destination: &mut [u8], source: &VirtualAddress) -> UserAccessResult<()> {
+	validate_range(source, destination.len(), false)?;
+	unsafe {
+		::core::ptr::copy_nonoverlapping(source.raw() as *const u8,
+		                                 destination.as_mut_ptr(), destination.len());
+	}
+	Ok(())
+}
+
+/// Copies `source` into the user range starting at `destination`
+pub fn copy_to_user(destination: &VirtualAddress, source: &[u8]) -> UserAccessResult<()> {
+	validate_range(destination, source.len(), true)?;
+	unsafe {
+		::core::ptr::copy_nonoverlapping(source.as_ptr(),
+		                                 destination.raw() as *mut u8, source.len());
+	}
+	Ok(())
+}
+
+/// Copies a user buffer into a new vector
+pub fn read_buffer(source: &VirtualAddress, size: usize) -> UserAccessResult<Vec<u8>> {
+	// The size comes from user mode, so nothing is allocated
+	// until the whole range is known to be mapped
+	validate_range(source, size, false)?;
+	let mut buffer = vec![0; size];
+	copy_from_user(&mut buffer, source)?;
+	Ok(buffer)
+}
+
+/// Copies a user string that is `size` bytes long
+pub fn read_string(source: &VirtualAddress, size: usize) -> UserAccessResult<String> {
+	let buffer = read_buffer(source, size)?;
+	String::from_utf8(buffer).map_err(|_| UserAccessError::InvalidString)
+}
+
+/// Copies a null terminated user string of at most `max_size` bytes
+pub fn read_c_string(source: &VirtualAddress, max_size: usize) -> UserAccessResult<String> {
+	use super::Page;
+	use super::PageLike;
+
+	// The string is read one page at a time because
+	// its length is unknown until the null is found
+	let mut data = Vec::new();
+	let mut address = source.raw();
+	while data.len() < max_size {
+		let page_end = (address & !(Page::SIZE as usize - 1)) + Page::SIZE as usize;
+		let size = (page_end - address).min(max_size - data.len());
+		let chunk = read_buffer(&VirtualAddress::new(address), size)?;
+		match chunk.iter().position(|byte| *byte == 0) {
+			Some(end) => {
+				data.extend_from_slice(&chunk[..end]);
+				return String::from_utf8(data).map_err(|_| UserAccessError::InvalidString);
+			}
+			None => data.extend_from_slice(&chunk),
+		}
+		address = page_end;
+	}
+	Err(UserAccessError::TooLarge)
+}
+
+/// Copies a null terminated user array of at most `max_count` pointers
+pub fn read_pointer_array(source: &VirtualAddress, max_count: usize) -> UserAccessResult<Vec<VirtualAddress>> {
+	use core::mem::size_of;
+	let mut pointers = Vec::new();
+	let mut address = source.clone();
+	while pointers.len() < max_count {
+		let mut pointer = [0; size_of::<u64>()];
+		copy_from_user(&mut pointer, &address)?;
+		let pointer = pointer.iter().rev().fold(0, |value, byte| (value << 8) | *byte as usize);
+		if pointer == 0 {
+			return Ok(pointers);
+		}
+
+		pointers.push(VirtualAddress::new(pointer));
+		address = address.offset(size_of::<u64>());
+	}
+	Err(UserAccessError::TooLarge)
+}
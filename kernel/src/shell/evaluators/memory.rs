@@ -13,8 +13,11 @@ pub fn construct() -> Evaluator {
 
 fn available() -> Traversal {
 	ClosureProcess::new_traversal(|| {
-		let free_frames = FrameLikeAllocator::<Frame>::free_frames_count(FRAME_ALLOCATOR.lock().deref());
-		let used_frames = FrameLikeAllocator::<Frame>::used_frames_count(FRAME_ALLOCATOR.lock().deref());
+		let (free_frames, used_frames) = ::interrupts::functions::without_interrupts(|| {
+			let allocator = FRAME_ALLOCATOR.lock();
+			(FrameLikeAllocator::<Frame>::free_frames_count(allocator.deref()),
+			 FrameLikeAllocator::<Frame>::used_frames_count(allocator.deref()))
+		});
 		let total_frames = free_frames + used_frames;
 		println!("{} megabytes total memory ({} frames)", total_frames / 256, total_frames);
 		println!("{} megabytes of free memory ({} frames)", free_frames / 256, free_frames);
@@ -31,34 +31,23 @@ fn is_good_frame(page: Page) -> bool {
 }
 
 fn allocate_frames(free_frames_count: usize) -> (u64, usize, Vec<Option<Frame>>) {
-	use paging::ACTIVE_PAGE_TABLE;
-	use paging::EntryFlags;
 	let mut frames: Vec<Option<Frame>> = vec![None; free_frames_count];
-
-	let mut frame_allocator = FRAME_ALLOCATOR.lock();
-	let frame_allocator = frame_allocator.deref_mut();
-	let mut page_table = ACTIVE_PAGE_TABLE.lock();
-	let page_table = page_table.deref_mut();
-
 	let mut allocation_count = 0;
 	let mut erroneous_frames_count = 0;
-	let temporary_page = Page::from_address(::paging::reserved::TEMPORARY_PAGE);
-	loop {
-		let frame: Frame;
-		if let Some(new_frame) = frame_allocator.allocate() {
-			frame = new_frame;
-		} else {
-			break;
-		}
+	while allocation_count < frames.len() {
+		// The tables are locked for one frame at a time so that the
+		// timer and other threads can still run between frames
+		let (frame, is_good) = match check_frame() {
+			Some(checked_frame) => checked_frame,
+			None => break,
+		};
 
-		page_table.map_to(temporary_page.clone(), frame, EntryFlags::WRITABLE, frame_allocator);
-		if is_good_frame(temporary_page.clone()) {
+		if is_good {
 			print!(".");
 		} else {
 			erroneous_frames_count += 1;
 			eprint!("x");
 		}
-		let frame = page_table.un_map(temporary_page.clone(), frame_allocator);
 
 		frames[allocation_count] = Some(frame);
 		allocation_count += 1;
@@ -70,6 +59,24 @@ fn allocate_frames(free_frames_count: usize) -> (u64, usize, Vec<Option<Frame>>)
 	(erroneous_frames_count, allocation_count, frames)
 }
 
+/// Allocates a frame and returns it with whether it is good
+fn check_frame() -> Option<(Frame, bool)> {
+	use paging::ACTIVE_PAGE_TABLE;
+	use paging::EntryFlags;
+
+	let mut frame_allocator = FRAME_ALLOCATOR.lock();
+	let frame_allocator = frame_allocator.deref_mut();
+	let mut page_table = ACTIVE_PAGE_TABLE.lock();
+	let page_table = page_table.deref_mut();
+
+	let frame = frame_allocator.allocate()?;
+	let temporary_page = Page::from_address(::paging::reserved::TEMPORARY_PAGE);
+	page_table.map_to(temporary_page.clone(), frame, EntryFlags::WRITABLE, frame_allocator);
+	let is_good = is_good_frame(temporary_page.clone());
+	let frame = page_table.un_map(temporary_page, frame_allocator);
+	Some((frame, is_good))
+}
+
 fn deallocate_frames(mut frames: Vec<Option<Frame>>) {
 	for allocated_frame in frames.iter_mut() {
 		let frame: Frame;
@@ -4,3 +4,4 @@ use super::Traversal;
 pub mod root;
 pub mod memory;
 pub mod memory_test;
+pub mod task;
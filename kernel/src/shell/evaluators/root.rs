@@ -4,5 +4,6 @@ use super::Traversal;
 pub fn construct() -> Evaluator {
 	let mut evaluator = Evaluator::new();
 	evaluator.add_option("memory", Traversal::Evaluator(super::memory::construct()));
+	evaluator.add_option("task", Traversal::Evaluator(super::task::construct()));
 	evaluator
 }
\ No newline at end of file
@@ -0,0 +1,61 @@
+use alloc::BTreeMap;
+use shell::ClosureProcess;
+use super::Evaluator;
+use super::Traversal;
+use task::functions;
+use task::ThreadId;
+use task::ThreadState;
+
+pub fn construct() -> Evaluator {
+	let mut evaluator = Evaluator::new();
+	evaluator.add_option("top", top());
+	evaluator
+}
+
+// Lists every thread once a second until a key is pressed
+// The usage column is the share of the ticks since the last refresh
+fn top() -> Traversal {
+	ClosureProcess::new_traversal(|| {
+		let frequency = ::interrupts::pit_functions::frequency() as usize;
+		let mut previous_ticks = functions::ticks();
+		let mut previous_running: BTreeMap<ThreadId, usize> = BTreeMap::new();
+		loop {
+			let ticks = functions::ticks();
+			let elapsed = (ticks - previous_ticks).max(1);
+			println!("{:>5} {:>9} {:>3} {:>5} {:>8} {:>9} {:>6} {:>6} {:>8}",
+			         "ID", "STATE", "PRI", "USE%", "TICKS", "SCHEDULED", "VOL", "PRE", "BLOCKED");
+			for summary in functions::thread_summaries() {
+				let statistics = &summary.statistics;
+				let previous = previous_running.insert(summary.id, statistics.running_ticks).unwrap_or(0);
+				let usage = (statistics.running_ticks - previous) * 100 / elapsed;
+				println!("{:>5} {:>9} {:>3} {:>5} {:>8} {:>9} {:>6} {:>6} {:>8}",
+				         summary.id, state_name(&summary.state), summary.priority, usage,
+				         statistics.running_ticks, statistics.scheduled_count, statistics.voluntary_switches,
+				         statistics.preemptive_switches, statistics.blocked_ticks(ticks));
+			}
+			println!();
+			previous_ticks = ticks;
+
+			let refresh_tick = ticks + frequency;
+			while functions::ticks() < refresh_tick {
+				// The release of the key that started the process is ignored
+				while let Some(key_event) = ::keyboard::functions::poll_key_event() {
+					if key_event.state == ::keyboard::KeyState::Pressed {
+						return;
+					}
+				}
+				functions::sleep_until(functions::ticks() + frequency / 10);
+			}
+		}
+	})
+}
+
+fn state_name(state: &ThreadState) -> &'static str {
+	match state {
+		ThreadState::Ready => "ready",
+		ThreadState::Sleeping(_) => "sleeping",
+		ThreadState::Waiting(_) => "waiting",
+		ThreadState::Blocked { .. } => "blocked",
+		ThreadState::Exited(_) => "exited",
+	}
+}
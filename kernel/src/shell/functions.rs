@@ -6,4 +6,14 @@ pub static SYSTEM_SHELL: Global<KernelShell> = Global::new("SYSTEM_SHELL");
 pub fn initialize() {
 	let _status = ::display::text_mode::BootStatus::new("Initializing kernel shell");
 	SYSTEM_SHELL.set(KernelShell::new());
+	::task::functions::spawn_kernel(run);
+}
+
+/// Handles key events on a kernel thread so that
+/// commands do not run in interrupt context
+fn run() {
+	loop {
+		let key_event = ::keyboard::functions::next_key_event();
+		SYSTEM_SHELL.lock().on_key_press(key_event);
+	}
 }
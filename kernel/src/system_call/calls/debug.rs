@@ -0,0 +1,8 @@
+use super::SystemCallFrame;
+use super::SystemCallResult;
+
+// Change this code to your desire!
+pub fn debug_value(frame: &mut SystemCallFrame) -> SystemCallResult {
+	println!("System call value: {:#x}", frame.argument(0));
+	Ok(0)
+}
@@ -0,0 +1,86 @@
+use alloc::boxed::Box;
+use alloc::Vec;
+use graph::Provider;
+use graph::Resource;
+use paging::user_access;
+use paging::VirtualAddress;
+use super::SystemCallError;
+use super::SystemCallFrame;
+use super::SystemCallResult;
+use task::functions::with_active_thread;
+use task::Handle;
+
+// Arguments: location pointer, location length
+// Returns: the handle of the opened resource
+pub fn open(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let location = VirtualAddress::new(frame.argument(0) as usize);
+	let location = user_access::read_string(&location, frame.argument(1) as usize)?;
+	let location = ::graph::Location::parse(&location);
+
+	// The provider is opened before the thread is locked so that
+	// interrupts remain enabled while the graph is traversed
+	let resource = ::graph::ROOT_PROVIDER.lock().open(&location.as_slice())
+	                                            .ok_or(SystemCallError::NotFound)?;
+	Ok(with_active_thread(|thread| thread.handles.insert(resource)))
+}
+
+// Arguments: handle, buffer pointer, buffer length
+// Returns: the number of bytes read
+pub fn read(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let handle = frame.argument(0);
+	let buffer = VirtualAddress::new(frame.argument(1) as usize);
+	let size = frame.argument(2) as usize;
+	user_access::validate_range(&buffer, size, true)?;
+
+	let mut data: Vec<u8> = vec![0; size];
+	let count = with_resource(handle, |resource| Ok(resource.read(&mut data)?))?;
+
+	user_access::copy_to_user(&buffer, &data[..count])?;
+	Ok(count as u64)
+}
+
+// Arguments: handle, buffer pointer, buffer length
+// Returns: the number of bytes written
+pub fn write(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let handle = frame.argument(0);
+	let buffer = VirtualAddress::new(frame.argument(1) as usize);
+	let data = user_access::read_buffer(&buffer, frame.argument(2) as usize)?;
+
+	with_resource(handle, |resource| {
+		resource.write(&data)?;
+		Ok(data.len() as u64)
+	})
+}
+
+// Arguments: handle, byte count
+// Returns: the number of bytes moved forward
+pub fn seek(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let handle = frame.argument(0);
+	let count = frame.argument(1) as usize;
+
+	with_resource(handle, |resource| Ok(resource.seek(count)? as u64))
+}
+
+// Arguments: handle
+pub fn close(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let handle = frame.argument(0);
+
+	// The resource is dropped after the thread is unlocked
+	let mut resource = with_active_thread(|thread| thread.handles.remove(handle))
+		.ok_or(SystemCallError::BadHandle)?;
+	resource.close()?;
+	Ok(0)
+}
+
+// The resource is taken out of the handle table while it is used, so
+// the thread is not locked with interrupts disabled during the operation
+// and the resource may park the thread. Only the thread itself uses its
+// handle table, so the handle cannot be reused in the meantime
+fn with_resource<F, R>(handle: Handle, f: F) -> Result<R, SystemCallError>
+	where F: FnOnce(&mut Box<Resource + Send>) -> Result<R, SystemCallError> {
+	let mut resource = with_active_thread(|thread| thread.handles.remove(handle))
+		.ok_or(SystemCallError::BadHandle)?;
+	let result = f(&mut resource);
+	with_active_thread(|thread| thread.handles.restore(handle, resource));
+	result
+}
@@ -0,0 +1,42 @@
+use core::mem::align_of;
+use paging::VirtualAddress;
+use super::SystemCallError;
+use super::SystemCallFrame;
+use super::SystemCallResult;
+use task::futex;
+use task::futex::WaitResult;
+
+// Arguments: word pointer, expected value, timeout in milliseconds
+// Waits until another thread wakes the word or the timeout, if it is
+// not zero, elapses. Fails with TryAgain if the word does not hold the
+// expected value, TimedOut if the timeout elapsed and Interrupted if a
+// signal was sent to the thread
+pub fn futex_wait(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let address = word_address(frame.argument(0))?;
+	let expected = frame.argument(1) as u32;
+	let timeout = match frame.argument(2) {
+		0 => None,
+		timeout => Some(::task::functions::ticks().saturating_add(super::process::to_ticks(timeout))),
+	};
+
+	match futex::wait(&address, expected, timeout)? {
+		WaitResult::Woken => Ok(0),
+		WaitResult::ValueChanged => Err(SystemCallError::TryAgain),
+		WaitResult::TimedOut => Err(SystemCallError::TimedOut),
+		WaitResult::Interrupted => Err(SystemCallError::Interrupted),
+	}
+}
+
+// Arguments: word pointer, maximum thread count
+// Returns: the number of threads that were woken
+pub fn futex_wake(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let address = word_address(frame.argument(0))?;
+	Ok(futex::wake(&address, frame.argument(1) as usize)? as u64)
+}
+
+fn word_address(address: u64) -> Result<VirtualAddress, SystemCallError> {
+	if address % align_of::<u32>() as u64 != 0 {
+		return Err(SystemCallError::InvalidArgument);
+	}
+	Ok(VirtualAddress::new(address as usize))
+}
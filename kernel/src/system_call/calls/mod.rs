@@ -0,0 +1,9 @@
+use super::SystemCallError;
+use super::SystemCallFrame;
+use super::SystemCallResult;
+
+pub mod debug;
+pub mod file;
+pub mod futex;
+pub mod process;
+pub mod signal;
\ No newline at end of file
@@ -0,0 +1,252 @@
+use alloc::String;
+use alloc::Vec;
+use paging::user_access;
+use paging::VirtualAddress;
+use super::SystemCallError;
+use super::SystemCallFrame;
+use super::SystemCallResult;
+use task::functions;
+use task::loaders::functions as loaders;
+use task::loaders::stack;
+use task::schedulers::Reservation;
+
+// Arguments: exit code
+// Does not return
+pub fn exit(frame: &mut SystemCallFrame) -> SystemCallResult {
+	functions::exit(frame.argument(0));
+}
+
+pub fn yield_now(_frame: &mut SystemCallFrame) -> SystemCallResult {
+	functions::yield_now();
+	Ok(0)
+}
+
+// Arguments: milliseconds
+// Fails with Interrupted if a signal was sent to the thread
+pub fn sleep(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let wake_tick = functions::ticks().saturating_add(to_ticks(frame.argument(0)));
+	functions::sleep_until(wake_tick);
+	match functions::ticks() < wake_tick {
+		true => Err(SystemCallError::Interrupted),
+		false => Ok(0),
+	}
+}
+
+// Returns: the nanoseconds since boot, which never decrease
+pub fn time(_frame: &mut SystemCallFrame) -> SystemCallResult {
+	Ok(functions::nanoseconds())
+}
+
+// Returns: the identifier of the calling thread
+pub fn get_id(_frame: &mut SystemCallFrame) -> SystemCallResult {
+	Ok(functions::with_active_thread(|thread| thread.id) as u64)
+}
+
+// Arguments: location pointer, location length, argument array, environment array
+// The arrays hold pointers to null terminated strings and end with
+// a null pointer. A null array is treated as empty
+// Returns: the identifier of the new thread
+pub fn spawn(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let binary = read_binary(frame.argument(0), frame.argument(1))?;
+	let (arguments, environment) = read_arguments(frame.argument(2), frame.argument(3))?;
+
+	// The binary is checked before an address space is created for it
+	loaders::validate_binary(&binary)?;
+	let thread = loaders::load_binary(&binary, functions::new_page_table(), &arguments, &environment)?;
+	Ok(functions::spawn(thread) as u64)
+}
+
+// Arguments: location pointer, location length, argument array, environment array
+// Replaces the program of the calling thread, keeping its identifier
+// and open handles. Does not return unless the binary is invalid
+// A binary that fails to load after the old program is freed
+// terminates the thread
+pub fn exec(frame: &mut SystemCallFrame) -> SystemCallResult {
+	use memory::Frame;
+	use memory::FrameLike;
+	use paging::PageLike;
+
+	let binary = read_binary(frame.argument(0), frame.argument(1))?;
+	let (arguments, environment) = read_arguments(frame.argument(2), frame.argument(3))?;
+	loaders::validate_binary(&binary)?;
+
+	// The kernel stack of the thread is in use, so
+	// it is the only page that is not cleared
+	let (mut table, kernel_stack) = functions::with_active_thread(|thread| {
+		(thread.page_table.clone(), thread.kernel_stack.clone())
+	});
+	let kernel_stack_frame = ::interrupts::functions::without_interrupts(|| {
+		::paging::ACTIVE_PAGE_TABLE.lock().translate(&kernel_stack.start_address())
+	}).map(Frame::from_address).expect("Kernel stack not mapped");
+	::paging::teardown::free_user_space(&mut table, Some(&kernel_stack_frame));
+
+	// The old program is gone, so there is nothing to return an error to
+	let (entry_point, thread_pointer) = match loaders::map_binary(&binary, &mut table) {
+		Ok(addresses) => addresses,
+		Err(_) => functions::exit(::task::signal::exit_code(::task::signal::SIGSEGV)),
+	};
+	let stack_top = stack::create_user_stack(::paging::reserved::USER_STACK_BOTTOM, &mut table);
+	let stack_pointer = loaders::write_arguments(&mut table, &stack_top, &entry_point, &arguments, &environment);
+	frame.reset(entry_point.raw() as u64, stack_pointer.raw() as u64);
+	functions::set_fs_base(thread_pointer.raw() as u64);
+
+	// The new program starts with the initial floating point state
+	functions::with_active_thread(|thread| {
+		use task::extended_state::ExtendedState;
+		thread.extended_state.release(thread.id);
+		thread.extended_state = ExtendedState::new();
+		thread.signals.reset_handlers();
+	});
+	Ok(0)
+}
+
+fn read_binary(location: u64, length: u64) -> Result<Vec<u8>, SystemCallError> {
+	use graph::Provider;
+	let location = VirtualAddress::new(location as usize);
+	let location = user_access::read_string(&location, length as usize)?;
+	let location = ::graph::Location::parse(&location);
+	let binary = ::graph::ROOT_PROVIDER.lock().open(&location.as_slice())
+	                                          .ok_or(SystemCallError::NotFound)?
+	                                          .read_all();
+	Ok(binary)
+}
+
+fn read_arguments(arguments: u64, environment: u64) -> Result<(Vec<String>, Vec<String>), SystemCallError> {
+	let arguments = read_string_array(arguments)?;
+	let environment = read_string_array(environment)?;
+
+	// Everything is placed on the user stack, so the total size is limited
+	let size: usize = arguments.iter().chain(environment.iter()).map(|string| string.len() + 1).sum();
+	if arguments.len() + environment.len() > stack::MAX_ARGUMENT_COUNT || size > stack::MAX_ARGUMENT_SIZE {
+		return Err(SystemCallError::TooLarge);
+	}
+	Ok((arguments, environment))
+}
+
+fn read_string_array(array: u64) -> Result<Vec<String>, SystemCallError> {
+	if array == 0 {
+		return Ok(Vec::new());
+	}
+
+	let array = VirtualAddress::new(array as usize);
+	let pointers = user_access::read_pointer_array(&array, stack::MAX_ARGUMENT_COUNT)?;
+	let mut strings = Vec::new();
+	for pointer in pointers {
+		strings.push(user_access::read_c_string(&pointer, stack::MAX_ARGUMENT_SIZE)?);
+	}
+	Ok(strings)
+}
+
+// Arguments: child identifier
+// Returns: the exit code of the child
+// Fails with Interrupted if a signal was sent to the thread
+pub fn wait(frame: &mut SystemCallFrame) -> SystemCallResult {
+	use task::functions::WaitError;
+	let child = frame.argument(0) as usize;
+	functions::wait(child).map_err(|error| match error {
+		WaitError::NoChild => SystemCallError::NoChild,
+		WaitError::Interrupted => SystemCallError::Interrupted,
+	})
+}
+
+// Returns: the identifier of the child to the parent and zero to the child
+// The child shares the memory of the parent until either writes to it
+// and starts without any open handles
+pub fn fork(frame: &mut SystemCallFrame) -> SystemCallResult {
+	use core::mem::size_of;
+	use paging::EntryFlags;
+	use paging::PageIter;
+	use paging::PageLike;
+	use task::Thread;
+
+	let mut table = functions::new_page_table();
+	::paging::copy_on_write::share_user_space(&mut table);
+
+	// The child gets its own kernel stack at the same address
+	let kernel_stack = functions::with_active_thread(|thread| thread.kernel_stack.clone());
+	let kernel_stack_region = PageIter::inclusive(kernel_stack.clone(), kernel_stack.clone());
+	loaders::allocate_region(kernel_stack_region, &mut table, EntryFlags::WRITABLE);
+
+	// The child is resumed in kernel mode to pop a copy of
+	// the frame, so it returns from the system call as well
+	let mut child_frame = frame.clone();
+	child_frame.set_return(0);
+	let frame_address = kernel_stack.end_address().raw() + 1 - size_of::<SystemCallFrame>();
+	let frame_address = VirtualAddress::new(frame_address);
+	let stack_pointer = frame_address.raw() - stack::INITIAL_STACK_SIZE * size_of::<u64>();
+	let stack_pointer = VirtualAddress::new(stack_pointer);
+
+	let resume_address = VirtualAddress::new(::interrupts::handlers::return_to_user as usize);
+	let stack_data = stack::create_kernel_stack(&resume_address, &frame_address);
+	loaders::write_data(::utility::convert::as_u8_slice(&stack_data), &mut table, stack_pointer.clone());
+	loaders::write_data(::utility::convert::as_u8_slice(&[child_frame]), &mut table, frame_address);
+
+	let mut thread = Thread::new(table, kernel_stack, stack_pointer);
+	functions::with_active_thread(|parent| {
+		parent.extended_state.synchronize(parent.id);
+		thread.extended_state = parent.extended_state.duplicate();
+		thread.priority = parent.priority;
+		thread.fs_base = parent.fs_base;
+		thread.signals = parent.signals.fork();
+	});
+	Ok(functions::spawn(thread) as u64)
+}
+
+// Arguments: thread identifier, priority
+// The thread must be the calling thread or one of its children
+pub fn set_priority(frame: &mut SystemCallFrame) -> SystemCallResult {
+	use task::thread::PRIORITY_COUNT;
+	let id = frame.argument(0) as usize;
+	let priority = frame.argument(1) as usize;
+	if priority >= PRIORITY_COUNT {
+		return Err(SystemCallError::InvalidArgument);
+	}
+
+	let related = functions::with_active_thread(|thread| thread.id == id || thread.children.contains(&id));
+	if !related || !functions::set_priority(id, priority) {
+		return Err(SystemCallError::NoChild);
+	}
+	Ok(0)
+}
+
+// Arguments: period in milliseconds, budget in milliseconds
+// The calling thread may run for the budget once every period and
+// is then scheduled before every thread without a reservation
+// A period of zero removes the reservation of the calling thread
+pub fn set_reservation(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let period = to_ticks(frame.argument(0));
+	let budget = to_ticks(frame.argument(1));
+	let reservation = match period {
+		0 => None,
+		_ if budget == 0 || budget > period => return Err(SystemCallError::InvalidArgument),
+		_ => {
+			let reservation = Reservation::new(period, budget, functions::ticks());
+			Some(reservation.ok_or(SystemCallError::InvalidArgument)?)
+		}
+	};
+
+	match functions::set_reservation(reservation) {
+		true => Ok(0),
+		false => Err(SystemCallError::Busy),
+	}
+}
+
+// Arguments: thread pointer
+// Changes the fs base of the calling thread, which
+// points to its thread control block. See task/loaders/tls
+pub fn set_fs_base(frame: &mut SystemCallFrame) -> SystemCallResult {
+	// Writing an address outside the lower half would fault
+	let fs_base = frame.argument(0);
+	if fs_base > ::paging::reserved::USER_SPACE_TOP.raw() as u64 {
+		return Err(SystemCallError::InvalidArgument);
+	}
+
+	functions::set_fs_base(fs_base);
+	Ok(0)
+}
+
+// The duration is rounded up to a whole number of ticks
+pub fn to_ticks(milliseconds: u64) -> usize {
+	let frequency = ::interrupts::pit_functions::frequency() as usize;
+	(milliseconds as usize).saturating_mul(frequency).saturating_add(999) / 1000
+}
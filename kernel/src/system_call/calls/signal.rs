@@ -0,0 +1,92 @@
+use super::SystemCallError;
+use super::SystemCallFrame;
+use super::SystemCallResult;
+use task::functions;
+use task::signal;
+use task::signal::SignalAction;
+
+// The handler values of sigaction that are not addresses
+const SIG_DFL: u64 = 0;
+const SIG_IGN: u64 = 1;
+
+// The ways sigprocmask can change the blocked signals
+const SIG_BLOCK: u64 = 0;
+const SIG_UNBLOCK: u64 = 1;
+const SIG_SETMASK: u64 = 2;
+
+// Arguments: signal, handler, restorer
+// A handler of zero restores the default action and one ignores the
+// signal. Otherwise the handler is called with the signal and returns
+// to the restorer, which must make the sigreturn system call
+// The action of SIGKILL cannot be changed
+pub fn sigaction(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let signal = frame.argument(0) as usize;
+	let action = match (frame.argument(1), frame.argument(2)) {
+		(SIG_DFL, _) => SignalAction::Default,
+		(SIG_IGN, _) => SignalAction::Ignore,
+		(handler, restorer) => {
+			let user_space_top = ::paging::reserved::USER_SPACE_TOP.raw() as u64;
+			if handler > user_space_top || restorer > user_space_top {
+				return Err(SystemCallError::InvalidArgument);
+			}
+			SignalAction::Handler { handler, restorer }
+		}
+	};
+
+	let changed = signal::is_valid(signal) &&
+		functions::with_active_thread(|thread| thread.signals.set_action(signal, action));
+	match changed {
+		true => Ok(0),
+		false => Err(SystemCallError::InvalidArgument),
+	}
+}
+
+// Arguments: how, signal mask
+// Blocks, unblocks or replaces the blocked signals, where the bit
+// of a signal is one below its number. SIGKILL cannot be blocked
+// Returns: the previously blocked signals
+pub fn sigprocmask(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let how = frame.argument(0);
+	let mask = frame.argument(1);
+	functions::with_active_thread(|thread| {
+		let blocked = thread.signals.blocked;
+		let new_blocked = match how {
+			SIG_BLOCK => blocked | mask,
+			SIG_UNBLOCK => blocked & !mask,
+			SIG_SETMASK => mask,
+			_ => return Err(SystemCallError::InvalidArgument),
+		};
+		thread.signals.set_blocked(new_blocked);
+		Ok(blocked)
+	})
+}
+
+// Returns from a signal handler to the code that it interrupted
+// Must be made with int 0xaa, as the syscall instruction
+// cannot restore the rcx and r11 registers. See task/signal
+pub fn sigreturn(frame: &mut SystemCallFrame) -> SystemCallResult {
+	// A thread that corrupted its signal frame cannot continue
+	if signal::restore(frame).is_err() {
+		functions::with_active_thread(|thread| thread.signals.force(signal::SIGSEGV));
+		return Err(SystemCallError::BadAddress);
+	}
+
+	// The dispatcher places the result in rax
+	Ok(frame.rax)
+}
+
+// Arguments: thread identifier, signal
+// The thread must be the calling thread or one of its children
+pub fn kill(frame: &mut SystemCallFrame) -> SystemCallResult {
+	let id = frame.argument(0) as usize;
+	let signal = frame.argument(1) as usize;
+	if !signal::is_valid(signal) {
+		return Err(SystemCallError::InvalidArgument);
+	}
+
+	let related = functions::with_active_thread(|thread| thread.id == id || thread.children.contains(&id));
+	if !related || !functions::send_signal(id, signal) {
+		return Err(SystemCallError::NoChild);
+	}
+	Ok(0)
+}
@@ -0,0 +1,71 @@
+use graph::ResourceError;
+use paging::user_access::UserAccessError;
+use task::loaders::elf_binary::ElfError;
+
+pub type SystemCallResult = Result<u64, SystemCallError>;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SystemCallError {
+	UnknownCall,
+	BadAddress,
+	InvalidArgument,
+	NotFound,
+	BadHandle,
+	NoChild,
+	InvalidExecutable,
+	TooLarge,
+	Busy,
+	TryAgain,
+	TimedOut,
+	Interrupted,
+}
+
+impl SystemCallError {
+	// The codes match the errno values used by Linux so that
+	// existing user space libraries can interpret them
+	pub fn code(&self) -> u64 {
+		match self {
+			SystemCallError::UnknownCall => 38,
+			SystemCallError::BadAddress => 14,
+			SystemCallError::InvalidArgument => 22,
+			SystemCallError::NotFound => 2,
+			SystemCallError::BadHandle => 9,
+			SystemCallError::NoChild => 10,
+			SystemCallError::InvalidExecutable => 8,
+			SystemCallError::TooLarge => 7,
+			SystemCallError::Busy => 16,
+			SystemCallError::TryAgain => 11,
+			SystemCallError::TimedOut => 110,
+			SystemCallError::Interrupted => 4,
+		}
+	}
+
+	/// Returns the value placed in rax for this error
+	pub fn encode(&self) -> u64 {
+		(-(self.code() as i64)) as u64
+	}
+}
+
+impl From<UserAccessError> for SystemCallError {
+	fn from(error: UserAccessError) -> SystemCallError {
+		match error {
+			UserAccessError::InvalidString => SystemCallError::InvalidArgument,
+			UserAccessError::TooLarge => SystemCallError::TooLarge,
+			_ => SystemCallError::BadAddress,
+		}
+	}
+}
+
+impl From<ResourceError> for SystemCallError {
+	fn from(error: ResourceError) -> SystemCallError {
+		match error {
+			ResourceError::Closed => SystemCallError::BadHandle,
+		}
+	}
+}
+
+impl From<ElfError> for SystemCallError {
+	fn from(_: ElfError) -> SystemCallError {
+		SystemCallError::InvalidExecutable
+	}
+}
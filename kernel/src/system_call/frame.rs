@@ -0,0 +1,70 @@
+// The general purpose registers are pushed by the system call entry
+// in interrupts/handlers::system_call_handler, directly below the
+// exception stack frame that the processor pushes. The fields are
+// in reverse order of the pushes because the stack grows downwards.
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct SystemCallFrame {
+	pub r15: u64,
+	pub r14: u64,
+	pub r13: u64,
+	pub r12: u64,
+	pub r11: u64,
+	pub r10: u64,
+	pub r9: u64,
+	pub r8: u64,
+	pub rbp: u64,
+	pub rdi: u64,
+	pub rsi: u64,
+	pub rdx: u64,
+	pub rcx: u64,
+	pub rbx: u64,
+	pub rax: u64,
+
+	pub instruction_pointer: u64,
+	pub code_segment: u64,
+	pub cpu_flags: u64,
+	pub stack_pointer: u64,
+	pub stack_segment: u64,
+}
+
+impl SystemCallFrame {
+	pub const ARGUMENT_COUNT: usize = 6;
+
+	pub fn number(&self) -> u64 {
+		self.rax
+	}
+
+	pub fn argument(&self, index: usize) -> u64 {
+		match index {
+			0 => self.rdi,
+			1 => self.rsi,
+			2 => self.rdx,
+			3 => self.r10,
+			4 => self.r8,
+			5 => self.r9,
+			_ => panic!("System calls only have {} arguments", Self::ARGUMENT_COUNT),
+		}
+	}
+
+	/// Sets the value of rax that the calling thread receives
+	pub fn set_return(&mut self, value: u64) {
+		self.rax = value;
+	}
+
+	/// Clears the registers so that the thread returns to the
+	/// start of a new program
+	pub fn reset(&mut self, instruction_pointer: u64, stack_pointer: u64) {
+		// Interrupt Enable Flag, Reserved
+		const R_FLAGS: u64 = 0b10_0000_0010;
+		*self = SystemCallFrame {
+			instruction_pointer,
+			code_segment: self.code_segment,
+			cpu_flags: R_FLAGS,
+			stack_pointer,
+			stack_segment: self.stack_segment,
+			..unsafe { ::core::mem::zeroed() }
+		};
+	}
+}
@@ -1,8 +1,116 @@
-// Change this code to your desire!
-pub fn system_call_hook(code: u64) {
-	if code == 0xdeadbeef {
-		println!("First");
-	} else {
-		eprintln!("Second {:#x}", code)
+use super::calls;
+use super::numbers;
+use super::SystemCallError;
+use super::SystemCallFrame;
+use super::SystemCallResult;
+use paging::VirtualAddress;
+
+pub type SystemCallHandler = fn(&mut SystemCallFrame) -> SystemCallResult;
+
+// The handler for a system call is found at the index
+// of its number. See system_call/numbers
+static SYSTEM_CALL_TABLE: [SystemCallHandler; numbers::COUNT] = [
+	calls::debug::debug_value,
+	calls::file::open,
+	calls::file::read,
+	calls::file::write,
+	calls::file::seek,
+	calls::file::close,
+	calls::process::exit,
+	calls::process::yield_now,
+	calls::process::sleep,
+	calls::process::get_id,
+	calls::process::spawn,
+	calls::process::wait,
+	calls::process::fork,
+	calls::process::exec,
+	calls::process::set_priority,
+	calls::process::set_reservation,
+	calls::process::time,
+	calls::process::set_fs_base,
+	calls::futex::futex_wait,
+	calls::futex::futex_wake,
+	calls::signal::sigaction,
+	calls::signal::sigprocmask,
+	calls::signal::sigreturn,
+	calls::signal::kill,
+];
+
+// The syscall instruction does not switch stacks, so the entry in
+// interrupts/handlers::fast_system_call_handler finds the kernel
+// stack of the active thread through this structure. The structure
+// is accessed with the gs segment after the swapgs instruction, so
+// the field offsets must match the ones used in the handler.
+#[repr(C)]
+pub struct FastCallState {
+	pub user_stack_pointer: u64,
+	pub kernel_stack_pointer: u64,
+	pub user_code_selector: u64,
+	pub user_data_selector: u64,
+}
+
+// This must be marked as mutable so it is placed in the .bss segment
+static mut FAST_CALL_STATE: FastCallState = FastCallState {
+	user_stack_pointer: 0,
+	kernel_stack_pointer: 0,
+	user_code_selector: 0,
+	user_data_selector: 0,
+};
+
+pub fn initialize() {
+	use interrupts::functions::{KERNEL_CODE_SELECTOR, USER_CODE_SELECTOR, USER_DATA_SELECTOR};
+	use x86_64::registers::msr::{IA32_FMASK, IA32_KERNEL_GS_BASE, IA32_LSTAR, IA32_STAR, wrmsr};
+
+	// Interrupts are disabled on entry so the timer cannot
+	// preempt the thread before the stack has been switched
+	const INTERRUPT_FLAG: u64 = 1 << 9;
+	const TRAP_FLAG: u64 = 1 << 8;
+	const DIRECTION_FLAG: u64 = 1 << 10;
+
+	let kernel_code_selector = *KERNEL_CODE_SELECTOR.try().unwrap() as u64;
+	let user_code_selector = *USER_CODE_SELECTOR.try().unwrap() as u64;
+	let user_data_selector = *USER_DATA_SELECTOR.try().unwrap() as u64;
+
+	// The syscall instruction loads the kernel data segment from the entry
+	// after the kernel code segment. The sysret instruction loads the user
+	// data segment and user code segment from the entries one and two
+	// after the selector base. See interrupts/functions::initialize_global_descriptor_table
+	assert_eq!(user_code_selector, user_data_selector + 8);
+	let sysret_base = user_data_selector - 8;
+
+	let handler = ::interrupts::handlers::fast_system_call_handler as unsafe extern "C" fn();
+	unsafe {
+		FAST_CALL_STATE.user_code_selector = user_code_selector;
+		FAST_CALL_STATE.user_data_selector = user_data_selector;
+
+		wrmsr(IA32_STAR, (sysret_base << 48) | (kernel_code_selector << 32));
+		wrmsr(IA32_LSTAR, handler as u64);
+		wrmsr(IA32_FMASK, INTERRUPT_FLAG | TRAP_FLAG | DIRECTION_FLAG);
+		wrmsr(IA32_KERNEL_GS_BASE, &FAST_CALL_STATE as *const _ as u64);
 	}
 }
+
+/// Sets the stack used when the active thread executes the syscall instruction
+pub fn set_kernel_stack(stack_top: VirtualAddress) {
+	unsafe { FAST_CALL_STATE.kernel_stack_pointer = stack_top.raw() as u64; }
+}
+
+/// Called from interrupts/handlers::system_call_handler and
+/// interrupts/handlers::fast_system_call_handler with a
+/// pointer to the registers saved on the kernel stack
+pub extern "C" fn dispatch(frame: &mut SystemCallFrame) {
+	let handler = SYSTEM_CALL_TABLE.get(frame.number() as usize);
+	let result = match handler {
+		Some(handler) => handler(frame),
+		None => Err(SystemCallError::UnknownCall),
+	};
+
+	let value = match result {
+		Ok(value) => value,
+		Err(error) => error.encode(),
+	};
+	frame.set_return(value);
+
+	// Signals raised during the call are delivered before returning
+	::task::signal::deliver_pending(frame);
+}
@@ -1 +1,19 @@
+pub use self::error::SystemCallError;
+pub use self::error::SystemCallResult;
+pub use self::frame::SystemCallFrame;
+
 pub mod functions;
+pub mod frame;
+pub mod error;
+pub mod numbers;
+pub mod calls;
+
+// A system call is made by placing the system call number in rax
+// and up to six arguments in rdi, rsi, rdx, r10, r8 and r9 before
+// executing
+//
+// int 0xaa
+//
+// or the faster syscall instruction, which clobbers rcx and r11.
+// The result is returned in rax. Negative values are error codes,
+// see system_call/error. The numbers are listed in system_call/numbers
@@ -0,0 +1,29 @@
+// Every system call number indexes into the table in
+// system_call/functions, so the numbers must be contiguous
+
+pub const DEBUG_VALUE: u64 = 0;
+pub const OPEN: u64 = 1;
+pub const READ: u64 = 2;
+pub const WRITE: u64 = 3;
+pub const SEEK: u64 = 4;
+pub const CLOSE: u64 = 5;
+pub const EXIT: u64 = 6;
+pub const YIELD: u64 = 7;
+pub const SLEEP: u64 = 8;
+pub const GET_ID: u64 = 9;
+pub const SPAWN: u64 = 10;
+pub const WAIT: u64 = 11;
+pub const FORK: u64 = 12;
+pub const EXEC: u64 = 13;
+pub const SET_PRIORITY: u64 = 14;
+pub const SET_RESERVATION: u64 = 15;
+pub const TIME: u64 = 16;
+pub const SET_FS_BASE: u64 = 17;
+pub const FUTEX_WAIT: u64 = 18;
+pub const FUTEX_WAKE: u64 = 19;
+pub const SIGACTION: u64 = 20;
+pub const SIGPROCMASK: u64 = 21;
+pub const SIGRETURN: u64 = 22;
+pub const KILL: u64 = 23;
+
+pub const COUNT: usize = 24;
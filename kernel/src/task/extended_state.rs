@@ -0,0 +1,202 @@
+use alloc::Vec;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use spin::Mutex;
+use super::ThreadId;
+
+// The floating point and vector registers of a thread are saved lazily
+// The task switched flag is set on every context switch, so the first
+// floating point instruction of a thread raises the device not available
+// exception. The handler saves the registers of the thread that last used
+// them and restores the registers of the active thread. Threads that do
+// not use floating point instructions never have their registers saved.
+//
+// The kernel is compiled without floating point instructions
+// See x86_64-example_os.json
+
+// The save area of the thread whose state is in the registers
+static OWNER: Mutex<Option<(ThreadId, usize)>> = Mutex::new(None);
+
+// XSAVE is used if the processor supports it, otherwise FXSAVE
+static USE_XSAVE: AtomicBool = AtomicBool::new(false);
+static AREA_SIZE: AtomicUsize = AtomicUsize::new(FXSAVE_AREA_SIZE);
+
+const FXSAVE_AREA_SIZE: usize = 512;
+const AREA_ALIGNMENT: usize = 64;
+
+// The default control words, which mask every floating point exception
+const DEFAULT_FPU_CONTROL: u16 = 0x037f;
+const DEFAULT_MXCSR: u32 = 0x1f80;
+
+/// The saved floating point and vector registers of a thread
+pub struct ExtendedState {
+	memory: Vec<u8>,
+}
+
+impl ExtendedState {
+	/// Creates a state with the registers in their initial state
+	pub fn new() -> ExtendedState {
+		let mut state = ExtendedState {
+			memory: vec![0; AREA_SIZE.load(Ordering::SeqCst) + AREA_ALIGNMENT],
+		};
+
+		// An XSAVE area with an empty header restores every component
+		// to its initial state, except for the control registers
+		let area = state.area();
+		unsafe {
+			*(area as *mut u16) = DEFAULT_FPU_CONTROL;
+			*(area.offset(24) as *mut u32) = DEFAULT_MXCSR;
+		}
+		state
+	}
+
+	/// Creates a copy of the state, which must be synchronized first
+	pub fn duplicate(&mut self) -> ExtendedState {
+		let mut state = ExtendedState::new();
+		let size = AREA_SIZE.load(Ordering::SeqCst);
+		unsafe { ::core::ptr::copy_nonoverlapping(self.area(), state.area(), size); }
+		state
+	}
+
+	fn area(&mut self) -> *mut u8 {
+		let address = self.memory.as_mut_ptr() as usize;
+		let offset = (AREA_ALIGNMENT - address % AREA_ALIGNMENT) % AREA_ALIGNMENT;
+		(address + offset) as *mut u8
+	}
+
+	/// Copies the registers of the thread into this state
+	/// if they have not been saved since it last ran
+	pub fn synchronize(&self, thread: ThreadId) {
+		::interrupts::functions::without_interrupts(|| {
+			let owner = OWNER.lock();
+			if let Some((owner, area)) = *owner {
+				if owner == thread {
+					unsafe {
+						clear_task_switched();
+						save(area);
+						set_task_switched();
+					}
+				}
+			}
+		})
+	}
+
+	/// Discards the registers of the thread so that
+	/// they are not saved into this state again
+	pub fn release(&self, thread: ThreadId) {
+		::interrupts::functions::without_interrupts(|| {
+			// The next floating point instruction must not
+			// use the registers that were discarded
+			let mut owner = OWNER.lock();
+			if owner.map_or(false, |(owner, _)| owner == thread) {
+				*owner = None;
+				unsafe { set_task_switched(); }
+			}
+		})
+	}
+}
+
+pub fn initialize() {
+	const OSFXSR: u64 = 1 << 9;
+	const OSXMMEXCPT: u64 = 1 << 10;
+	const OSXSAVE: u64 = 1 << 18;
+	const XSAVE_SUPPORTED: u32 = 1 << 26;
+	const AVX_SUPPORTED: u32 = 1 << 28;
+	const MONITOR_COPROCESSOR: u64 = 1 << 1;
+	const EMULATE_COPROCESSOR: u64 = 1 << 2;
+
+	let (_, _, features, _) = cpuid(1, 0);
+	unsafe {
+		let cr0: u64;
+		asm!("mov $0, cr0" : "=r"(cr0) ::: "intel", "volatile");
+		let cr0 = (cr0 | MONITOR_COPROCESSOR) & !EMULATE_COPROCESSOR;
+		asm!("mov cr0, $0" :: "r"(cr0) :: "intel", "volatile");
+
+		let mut cr4: u64;
+		asm!("mov $0, cr4" : "=r"(cr4) ::: "intel", "volatile");
+		cr4 |= OSFXSR | OSXMMEXCPT;
+		if features & XSAVE_SUPPORTED != 0 {
+			cr4 |= OSXSAVE;
+		}
+		asm!("mov cr4, $0" :: "r"(cr4) :: "intel", "volatile");
+	}
+
+	if features & XSAVE_SUPPORTED != 0 {
+		// The x87 and SSE states are always enabled
+		const X87_STATE: u32 = 1 << 0;
+		const SSE_STATE: u32 = 1 << 1;
+		const AVX_STATE: u32 = 1 << 2;
+		let mut components = X87_STATE | SSE_STATE;
+		if features & AVX_SUPPORTED != 0 {
+			components |= AVX_STATE;
+		}
+
+		unsafe {
+			asm!("xsetbv" :: "{ecx}"(0), "{eax}"(components), "{edx}"(0) :: "intel", "volatile");
+		}
+
+		// The size of the area for the enabled components
+		let (_, area_size, _, _) = cpuid(0xd, 0);
+		AREA_SIZE.store(area_size as usize, Ordering::SeqCst);
+		USE_XSAVE.store(true, Ordering::SeqCst);
+	}
+}
+
+/// Called on every context switch so that the next floating
+/// point instruction raises the device not available exception
+pub fn on_switch() {
+	unsafe { set_task_switched(); }
+}
+
+/// Called by the device not available exception handler
+pub fn on_device_not_available(thread: ThreadId, state: &mut ExtendedState) {
+	unsafe { clear_task_switched(); }
+	let mut owner = OWNER.lock();
+	if owner.map_or(false, |(owner, _)| owner == thread) {
+		return;
+	}
+
+	let area = state.area() as usize;
+	unsafe {
+		if let Some((_, owner_area)) = *owner {
+			save(owner_area);
+		}
+		restore(area);
+	}
+	*owner = Some((thread, area));
+}
+
+fn cpuid(leaf: u32, subleaf: u32) -> (u32, u32, u32, u32) {
+	let (eax, ebx, ecx, edx): (u32, u32, u32, u32);
+	unsafe {
+		asm!("cpuid" : "={eax}"(eax), "={ebx}"(ebx), "={ecx}"(ecx), "={edx}"(edx)
+		             : "{eax}"(leaf), "{ecx}"(subleaf) :: "intel");
+	}
+	(eax, ebx, ecx, edx)
+}
+
+unsafe fn save(area: usize) {
+	match USE_XSAVE.load(Ordering::SeqCst) {
+		true => asm!("xsave64 [$0]" :: "r"(area), "{eax}"(!0_u32), "{edx}"(!0_u32) : "memory" : "intel", "volatile"),
+		false => asm!("fxsave64 [$0]" :: "r"(area) : "memory" : "intel", "volatile"),
+	}
+}
+
+unsafe fn restore(area: usize) {
+	match USE_XSAVE.load(Ordering::SeqCst) {
+		true => asm!("xrstor64 [$0]" :: "r"(area), "{eax}"(!0_u32), "{edx}"(!0_u32) : "memory" : "intel", "volatile"),
+		false => asm!("fxrstor64 [$0]" :: "r"(area) : "memory" : "intel", "volatile"),
+	}
+}
+
+unsafe fn set_task_switched() {
+	const TASK_SWITCHED: u64 = 1 << 3;
+	let cr0: u64;
+	asm!("mov $0, cr0" : "=r"(cr0) ::: "intel", "volatile");
+	asm!("mov cr0, $0" :: "r"(cr0 | TASK_SWITCHED) :: "intel", "volatile");
+}
+
+unsafe fn clear_task_switched() {
+	asm!("clts" :::: "intel", "volatile");
+}
@@ -1,18 +1,72 @@
 use alloc::boxed::Box;
+use alloc::BTreeMap;
+use alloc::BTreeSet;
+use alloc::Vec;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use paging::InactivePageTable;
 use paging::VirtualAddress;
+use super::Priority;
 use super::Scheduler;
 use super::Thread;
+use super::ThreadId;
+use super::ThreadState;
+use super::ThreadStatistics;
+use utility::BootOptions;
 use utility::Global;
 
-pub static ACTIVE_THREAD: Global<Thread> = Global::new("ACTIVE_THREAD");
-pub static SCHEDULER: Global<Box<Scheduler + Send>> = Global::new("SCHEDULER");
+// Globals that are locked in context_switch are interrupt safe
+pub static ACTIVE_THREAD: Global<Thread> = Global::new_irq_safe("ACTIVE_THREAD");
+pub static SCHEDULER: Global<Box<Scheduler + Send>> = Global::new_irq_safe("SCHEDULER");
 
-pub fn initialize() {
+// Every new address space is a shallow clone of this table
+// so that the kernel is mapped in every thread
+pub static BASE_TABLE: Global<InactivePageTable> = Global::new("BASE_TABLE");
+
+// Threads that are sleeping or waiting are kept out of the scheduler
+// and exited threads are kept until their parent collects the exit code
+static PARKED_THREADS: Global<Vec<Thread>> = Global::new_irq_safe("PARKED_THREADS");
+static EXITED_THREADS: Global<BTreeMap<ThreadId, Thread>> = Global::new_irq_safe("EXITED_THREADS");
+
+// Threads whose parent has exited are freed as soon as they exit
+// Threads cannot be freed during a context switch (see switch_thread)
+// so they are freed by the next spawn, wait or exit instead
+static ORPHANED_THREADS: Global<BTreeSet<ThreadId>> = Global::new("ORPHANED_THREADS");
+
+// Sleeping threads ordered by the tick they wake at
+// The list is checked on every timer interrupt
+static SLEEPING_THREADS: Global<BTreeSet<(usize, ThreadId)>> = Global::new_irq_safe("SLEEPING_THREADS");
+
+// The idle thread is kept out of the scheduler and
+// is only resumed when no other thread is ready
+static IDLE_THREAD: Global<Thread> = Global::new_irq_safe("IDLE_THREAD");
+
+// The number of timer interrupts since the scheduler started
+// and how many of those interrupted the idle thread
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+static IDLE_TICKS: AtomicUsize = AtomicUsize::new(0);
+
+// The identifier of the active thread can be read without locking
+// the ACTIVE_THREAD. It is zero before the first context switch
+static ACTIVE_THREAD_ID: AtomicUsize = AtomicUsize::new(0);
+
+// The share of the processor that is reserved by threads in
+// the earliest deadline class. See schedulers::earliest_deadline
+static RESERVED_UTILISATION: AtomicUsize = AtomicUsize::new(0);
+
+pub fn initialize(base_table: InactivePageTable, options: &BootOptions) {
 	let _status = ::display::text_mode::BootStatus::new("Creating preemptive scheduler");
 	enable_cpu_features();
+	super::extended_state::initialize();
+	super::kernel_stack::initialize();
 
-	let scheduler = super::schedulers::RoundRobin::new();
-	SCHEDULER.set(box scheduler);
+	SCHEDULER.set(super::schedulers::create_scheduler(options.get("scheduler")));
+	BASE_TABLE.set(base_table);
+	PARKED_THREADS.set(Vec::new());
+	EXITED_THREADS.set(BTreeMap::new());
+	ORPHANED_THREADS.set(BTreeSet::new());
+	SLEEPING_THREADS.set(BTreeSet::new());
+	IDLE_THREAD.set(create_idle_thread());
 }
 
 pub fn pre_initialize() {
@@ -27,19 +81,67 @@ pub fn pre_initialize() {
 
 	// We use a separate stack when facilitating a context switch
 	// See interrupts/handlers::timer_handler
-	let stack_start = Page::from_address(::paging::reserved::TASK_SWITCH_STACK_BOTTOM);
-	let stack_end = Page::from_address(::paging::reserved::TASK_SWITCH_STACK_TOP);
-	let pages = ::paging::PageIter::inclusive(stack_start, stack_end);
-	for page in pages {
-		let frame = allocator.allocate().expect("Out of memory: CONTEXT_SWITCH_STACK_ALLOCATION");
-		active_table.map_to(page, frame, ::paging::EntryFlags::WRITABLE, allocator.deref_mut());
+	// The idle thread also has its stack in the kernel half so
+	// that it is mapped in every address space
+	let stacks = [
+		(::paging::reserved::TASK_SWITCH_STACK_BOTTOM, ::paging::reserved::TASK_SWITCH_STACK_TOP),
+		(::paging::reserved::IDLE_STACK_BOTTOM, ::paging::reserved::IDLE_STACK_TOP),
+	];
+	for &(ref stack_bottom, ref stack_top) in stacks.iter() {
+		let stack_start = Page::from_address(stack_bottom.clone());
+		let stack_end = Page::from_address(stack_top.clone());
+		let pages = ::paging::PageIter::inclusive(stack_start, stack_end);
+		for page in pages {
+			let frame = allocator.allocate().expect("Out of memory: CONTEXT_SWITCH_STACK_ALLOCATION");
+			active_table.map_to(page, frame, ::paging::EntryFlags::WRITABLE, allocator.deref_mut());
+		}
+	}
+}
+
+fn create_idle_thread() -> Thread {
+	use core::mem::size_of;
+	use paging::Page;
+	use paging::PageLike;
+	use super::loaders::stack;
+
+	// The idle thread runs in kernel mode, so it starts from
+	// a kernel mode resume frame at the top of its stack
+	let stack_end = ::paging::reserved::IDLE_STACK_TOP.raw() + 1;
+	let stack_pointer = VirtualAddress::new(stack_end - stack::INITIAL_STACK_SIZE * size_of::<u64>());
+	let stack_data = stack::create_kernel_stack(&VirtualAddress::new(idle as usize), &stack_pointer);
+	unsafe { *(stack_pointer.raw() as *mut [u64; stack::INITIAL_STACK_SIZE]) = stack_data; }
+
+	let kernel_stack = Page::from_address(::paging::reserved::IDLE_STACK_BOTTOM);
+	Thread::new_idle(new_page_table(), kernel_stack, stack_pointer)
+}
+
+/// Halts until the next interrupt, forever
+extern "C" fn idle() -> ! {
+	loop {
+		// The thread starts with interrupts disabled
+		// See loaders/stack::create_kernel_stack
+		unsafe { asm!("sti
+		               hlt" :::: "intel", "volatile"); }
 	}
 }
 
+/// Runs the closure with the active thread
+///
+/// Interrupts are disabled while the closure runs so
+/// that the timer does not switch threads while the
+/// active thread is locked
+pub fn with_active_thread<F, R>(f: F) -> R where F: FnOnce(&mut Thread) -> R {
+	use core::ops::DerefMut;
+	::interrupts::functions::without_interrupts(|| {
+		let mut thread = ACTIVE_THREAD.lock();
+		f(thread.deref_mut())
+	})
+}
+
 fn enable_cpu_features() {
-	// This enables usage of the "sysenter" instruction
-	// However, system calls in this kernel currently
-	// utilize the int 0xaa instruction instead
+	// This enables usage of the "syscall" instruction
+	// User mode threads can use either the syscall
+	// instruction or the int 0xaa instruction
 
 	use x86_64::registers::msr::{IA32_EFER, rdmsr, wrmsr};
 	const SCE_BIT: u64 = 1;
@@ -47,35 +149,635 @@ fn enable_cpu_features() {
 		let efer = rdmsr(IA32_EFER);
 		wrmsr(IA32_EFER, efer | SCE_BIT);
 	}
+	::system_call::functions::initialize();
+}
+
+pub fn ticks() -> usize {
+	TICKS.load(Ordering::SeqCst)
+}
+
+/// The nanoseconds since the timer was started
+///
+/// Unlike the tick count, this includes the time
+/// since the last timer interrupt
+pub fn nanoseconds() -> u64 {
+	::interrupts::functions::without_interrupts(::interrupts::pit_functions::nanoseconds)
+}
+
+pub fn active_thread_id() -> ThreadId {
+	ACTIVE_THREAD_ID.load(Ordering::SeqCst)
+}
+
+/// The number of ticks that were spent in the idle thread
+pub fn idle_ticks() -> usize {
+	IDLE_TICKS.load(Ordering::SeqCst)
+}
+
+/// Gives up the remainder of the active thread's time slice
+///
+/// This must only be called from kernel mode
+pub fn yield_now() {
+	// See interrupts/handlers::yield_handler
+	unsafe { asm!("int 0xab" :::: "intel", "volatile"); }
+}
+
+/// Returns true if the active thread can be parked
+///
+/// There is no thread to park before the first context switch and
+/// the interrupted thread must not be parked by interrupt handlers
+pub fn can_block() -> bool {
+	::interrupts::functions::interrupts_enabled() &&
+		::interrupts::functions::without_interrupts(|| ACTIVE_THREAD.lock_direct().is_some())
+}
+
+/// Parks the active thread until it is woken if the closure returns true
+///
+/// The thread can be resumed before its condition holds,
+/// so the caller must check the condition again
+///
+/// The closure is given the identifier of the active thread and runs
+/// with interrupts disabled, so it can register the thread to be woken
+/// without an interrupt handler waking it before it is parked. It must
+/// not use the active thread
+pub fn block_on<F>(state: ThreadState, register: F) where F: FnOnce(ThreadId) -> bool {
+	park(state, false, register);
+}
+
+/// Parks the active thread like block_on unless it has a signal to
+/// deliver. A signal sent while the thread is parked wakes it, so the
+/// caller must check signal::is_interrupted. See send_signal
+pub fn block_on_interruptible<F>(state: ThreadState, register: F) where F: FnOnce(ThreadId) -> bool {
+	park(state, true, register);
+}
+
+fn park<F>(state: ThreadState, interruptible: bool, register: F) where F: FnOnce(ThreadId) -> bool {
+	let blocked = with_active_thread(|thread| {
+		// The signal is checked with the thread locked so that
+		// a signal sent before it is parked is not missed
+		if interruptible && thread.signals.is_interrupted() {
+			return false;
+		}
+
+		// Signals only wake blocked threads whose caller checks
+		// for them, see send_signal
+		let blocked = register(thread.id);
+		if blocked {
+			thread.state = match state {
+				ThreadState::Blocked { .. } => ThreadState::Blocked { interruptible },
+				state => state,
+			};
+		}
+		blocked
+	});
+
+	if blocked {
+		yield_now();
+		with_active_thread(|thread| thread.state = ThreadState::Ready);
+	}
+}
+
+/// Parks the active thread like block_on_interruptible until
+/// it is woken or the tick count reaches the wake tick
+pub fn block_on_until<F>(wake_tick: usize, register: F) where F: FnOnce(ThreadId) -> bool {
+	use core::cell::Cell;
+	let registered = Cell::new(None);
+	block_on_interruptible(ThreadState::Blocked { interruptible: true }, |id| {
+		if !register(id) {
+			return false;
+		}
+
+		// The timer wakes the thread in wake_sleeping_threads
+		SLEEPING_THREADS.lock().insert((wake_tick, id));
+		registered.set(Some(id));
+		true
+	});
+
+	// The entry is left behind if the thread was woken before the timeout
+	if let Some(id) = registered.get() {
+		::interrupts::functions::without_interrupts(|| SLEEPING_THREADS.lock().remove(&(wake_tick, id)));
+	}
+}
+
+/// Makes a blocked or sleeping thread ready
+///
+/// This can be called from interrupt handlers
+/// Returns false if the thread was not parked
+pub fn wake(id: ThreadId) -> bool {
+	let parked = |thread: &Thread| match thread.state {
+		ThreadState::Blocked { .. } | ThreadState::Sleeping(_) => true,
+		_ => false,
+	};
+
+	::interrupts::functions::without_interrupts(|| {
+		// The thread may not have yielded yet, see block_on
+		if let Some(thread) = ACTIVE_THREAD.lock_direct().as_mut() {
+			if thread.id == id {
+				let woken = parked(thread);
+				if woken {
+					thread.state = ThreadState::Ready;
+				}
+				return woken;
+			}
+		}
+
+		// The scheduler is locked first like in switch_thread
+		let mut scheduler = SCHEDULER.lock();
+		let mut parked_threads = PARKED_THREADS.lock();
+		match parked_threads.iter().position(|thread| thread.id == id && parked(thread)) {
+			Some(index) => {
+				let mut thread = parked_threads.remove(index);
+				thread.state = ThreadState::Ready;
+				scheduler.schedule_new(thread);
+				true
+			}
+			None => false,
+		}
+	})
+}
+
+/// Parks the active thread until the tick count reaches the wake tick
+/// or it has a signal to deliver
+pub fn sleep_until(wake_tick: usize) {
+	use core::cell::Cell;
+	while ticks() < wake_tick && !super::signal::is_interrupted() {
+		let registered = Cell::new(None);
+		block_on_interruptible(ThreadState::Sleeping(wake_tick), |id| {
+			SLEEPING_THREADS.lock().insert((wake_tick, id));
+			registered.set(Some(id));
+			true
+		});
+
+		// The entry is left behind if the thread was woken early, which
+		// would later wake the thread while it is parked for another reason
+		if let Some(id) = registered.get() {
+			::interrupts::functions::without_interrupts(|| SLEEPING_THREADS.lock().remove(&(wake_tick, id)));
+		}
+	}
+}
+
+/// Wakes the sleeping threads whose wake tick has been reached
+///
+/// Returns true if any thread was woken
+fn wake_sleeping_threads() -> bool {
+	// The sleeping threads are not locked while a thread is woken
+	// because switch_thread locks them while holding the scheduler
+	let ticks = ticks();
+	let mut woken = false;
+	loop {
+		let next = SLEEPING_THREADS.lock().iter().next().cloned();
+		match next {
+			Some((wake_tick, id)) if wake_tick <= ticks => {
+				SLEEPING_THREADS.lock().remove(&(wake_tick, id));
+				woken |= wake(id);
+			}
+			_ => return woken,
+		}
+	}
+}
+
+/// Creates a page table that only maps the kernel
+pub fn new_page_table() -> InactivePageTable {
+	use core::ops::DerefMut;
+	::interrupts::functions::without_interrupts(|| {
+		let mut active_table = ::paging::ACTIVE_PAGE_TABLE.lock();
+		let mut allocator = ::memory::FRAME_ALLOCATOR.lock();
+		BASE_TABLE.lock().clone_shallow(&mut active_table, allocator.deref_mut())
+	})
+}
+
+/// Adds a new thread as a child of the active thread
+pub fn spawn(mut thread: Thread) -> ThreadId {
+	reap_orphans();
+	let identifier = thread.id;
+	with_active_thread(|parent| {
+		thread.parent = Some(parent.id);
+		parent.children.insert(identifier);
+		SCHEDULER.lock().schedule_new(thread);
+	});
+	identifier
+}
+
+/// Changes the priority of a thread that has not exited
+///
+/// Returns false if no such thread exists
+pub fn set_priority(id: ThreadId, priority: Priority) -> bool {
+	::interrupts::functions::without_interrupts(|| {
+		let mut active_thread = ACTIVE_THREAD.lock();
+		if active_thread.id == id {
+			active_thread.priority = priority;
+			return true;
+		}
+
+		// Queued threads are queued again so that
+		// the scheduler sees the new priority
+		let mut scheduler = SCHEDULER.lock();
+		if let Some(mut thread) = scheduler.remove(id) {
+			thread.priority = priority;
+			scheduler.requeue(thread);
+			return true;
+		}
+
+		let mut parked_threads = PARKED_THREADS.lock();
+		match parked_threads.iter_mut().find(|thread| thread.id == id) {
+			Some(thread) => {
+				thread.priority = priority;
+				true
+			}
+			None => false,
+		}
+	})
+}
+
+/// Makes the signal pending in a thread that has not exited
+/// The thread receives it when it next returns to user mode
+///
+/// A parked thread is woken if the signal is delivered to it, so that
+/// its blocking system call is interrupted. Returns false if no such
+/// thread exists
+pub fn send_signal(id: ThreadId, signal: super::signal::Signal) -> bool {
+	// Sleeping and waiting threads are always parked interruptibly
+	let interrupted = |thread: &Thread| thread.signals.is_interrupted() && match thread.state {
+		ThreadState::Sleeping(_) | ThreadState::Waiting(_) => true,
+		ThreadState::Blocked { interruptible } => interruptible,
+		_ => false,
+	};
+
+	::interrupts::functions::without_interrupts(|| {
+		// The thread may not have yielded yet, see block_on
+		let mut active_thread = ACTIVE_THREAD.lock();
+		if active_thread.id == id {
+			active_thread.signals.raise(signal);
+			if interrupted(&*active_thread) {
+				active_thread.state = ThreadState::Ready;
+			}
+			return true;
+		}
+
+		// Queued threads are left in place in the scheduler
+		let mut scheduler = SCHEDULER.lock();
+		if scheduler.with_thread(id, &mut |thread| thread.signals.raise(signal)) {
+			return true;
+		}
+
+		let mut parked_threads = PARKED_THREADS.lock();
+		let index = match parked_threads.iter().position(|thread| thread.id == id) {
+			Some(index) => index,
+			None => return false,
+		};
+
+		parked_threads[index].signals.raise(signal);
+		if interrupted(&parked_threads[index]) {
+			let mut thread = parked_threads.remove(index);
+			thread.state = ThreadState::Ready;
+			scheduler.schedule_new(thread);
+		}
+		true
+	})
+}
+
+/// Gives the active thread the reservation or,
+/// if there is none, removes its reservation
+///
+/// Returns false if admitting the reservation would
+/// cause threads to miss their deadlines
+pub fn set_reservation(reservation: Option<super::schedulers::Reservation>) -> bool {
+	use super::schedulers::earliest_deadline::admit;
+	with_active_thread(|thread| {
+		let current = thread.reservation.map_or(0, |reservation| reservation.utilisation());
+		let admitted = RESERVED_UTILISATION.load(Ordering::SeqCst) - current;
+		let reservation = match reservation {
+			Some(reservation) => reservation,
+			None => {
+				RESERVED_UTILISATION.store(admitted, Ordering::SeqCst);
+				thread.reservation = None;
+				return true;
+			}
+		};
+
+		match admit(admitted, &reservation) {
+			Some(utilisation) => {
+				RESERVED_UTILISATION.store(utilisation, Ordering::SeqCst);
+				thread.reservation = Some(reservation);
+				true
+			}
+			None => false,
+		}
+	})
+}
+
+/// Changes the thread pointer of the active thread
+pub fn set_fs_base(fs_base: u64) {
+	with_active_thread(|thread| {
+		thread.fs_base = fs_base;
+		write_fs_base(fs_base);
+	})
+}
+
+fn write_fs_base(fs_base: u64) {
+	// The fs base is only used by user mode, so it
+	// does not matter which thread's base the kernel runs with
+	const IA32_FS_BASE: u32 = 0xc000_0100;
+	unsafe { ::x86_64::registers::msr::wrmsr(IA32_FS_BASE, fs_base); }
+}
+
+/// Creates a thread that runs the function in kernel mode
+///
+/// Kernel threads have no parent and are freed when they exit
+pub fn spawn_kernel<F>(function: F) -> ThreadId where F: FnOnce() + Send + 'static {
+	use core::mem::size_of;
+	use paging::Page;
+	use paging::PageLike;
+	use super::kernel_stack::KernelStack;
+	use super::loaders::stack;
+
+	let mut function = Some(function);
+	let entry: Box<FnMut() + Send> = box move || (function.take().unwrap())();
+
+	let kernel_thread_stack = KernelStack::new().expect("Out of kernel stack slots");
+	let stack_end = kernel_thread_stack.end();
+	let stack_pointer = VirtualAddress::new(stack_end - stack::INITIAL_STACK_SIZE * size_of::<u64>());
+
+	// The entry is resumed as though it had been called
+	let entry_stack_pointer = VirtualAddress::new((stack_pointer.raw() & !0xf) - size_of::<u64>());
+	let stack_data = stack::create_kernel_stack(&VirtualAddress::new(kernel_entry as usize), &entry_stack_pointer);
+	unsafe { *(stack_pointer.raw() as *mut [u64; stack::INITIAL_STACK_SIZE]) = stack_data; }
+
+	let kernel_stack = Page::from_address(stack_pointer.clone());
+	let mut thread = Thread::new(new_page_table(), kernel_stack, stack_pointer);
+	thread.kernel_entry = Some(entry);
+	thread.kernel_thread_stack = Some(kernel_thread_stack);
+
+	let identifier = thread.id;
+	::interrupts::functions::without_interrupts(|| SCHEDULER.lock().schedule_new(thread));
+	identifier
+}
+
+extern "C" fn kernel_entry() -> ! {
+	// The thread starts with interrupts disabled
+	// See loaders/stack::create_kernel_stack
+	unsafe { asm!("sti" :::: "intel", "volatile"); }
+	let mut entry = with_active_thread(|thread| thread.kernel_entry.take())
+		.expect("Kernel thread has no entry");
+	entry();
+	exit(0);
+}
+
+/// A snapshot of a thread for display
+pub struct ThreadSummary {
+	pub id: ThreadId,
+	pub state: ThreadState,
+	pub priority: Priority,
+	pub statistics: ThreadStatistics,
+}
+
+/// Returns a snapshot of every thread, ordered by identifier
+pub fn thread_summaries() -> Vec<ThreadSummary> {
+	let summary = |thread: &Thread| ThreadSummary {
+		id: thread.id,
+		state: thread.state,
+		priority: thread.priority,
+		statistics: thread.statistics,
+	};
+
+	let mut summaries = ::interrupts::functions::without_interrupts(|| {
+		let mut summaries = Vec::new();
+		summaries.extend(ACTIVE_THREAD.lock_direct().iter().map(&summary));
+		summaries.extend(IDLE_THREAD.lock_direct().iter().map(&summary));
+		SCHEDULER.lock().for_each(&mut |thread| summaries.push(summary(thread)));
+		summaries.extend(PARKED_THREADS.lock().iter().map(&summary));
+		summaries.extend(EXITED_THREADS.lock().values().map(&summary));
+		summaries
+	});
+	summaries.sort_by_key(|summary| summary.id);
+	summaries
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WaitError {
+	NoChild,
+	/// The active thread has a signal to deliver
+	Interrupted,
+}
+
+/// Blocks until the child exits and returns its exit code
+pub fn wait(child: ThreadId) -> Result<u64, WaitError> {
+	reap_orphans();
+	if !with_active_thread(|thread| thread.children.contains(&child)) {
+		return Err(WaitError::NoChild);
+	}
+
+	loop {
+		let exited = ::interrupts::functions::without_interrupts(|| EXITED_THREADS.lock().remove(&child));
+		if let Some(exited) = exited {
+			with_active_thread(|thread| thread.children.remove(&child));
+			let exit_code = match exited.state {
+				ThreadState::Exited(exit_code) => exit_code,
+				_ => unreachable!(),
+			};
+			return Ok(exit_code);
+		}
+
+		if super::signal::is_interrupted() {
+			return Err(WaitError::Interrupted);
+		}
+		block_on_interruptible(ThreadState::Waiting(child), |_| true);
+	}
+}
+
+/// Releases the resources of the active thread and switches away from it
+pub fn exit(exit_code: u64) -> ! {
+	use core::mem::replace;
+	use super::HandleTable;
+
+	// Resources are released here, with interrupts enabled, as a context
+	// switch must not deallocate while it holds the page table lock
+	let (handles, orphans) = with_active_thread(|thread| {
+		if let Some(reservation) = thread.reservation.take() {
+			RESERVED_UTILISATION.fetch_sub(reservation.utilisation(), Ordering::SeqCst);
+		}
+
+		let mut orphans = thread.children.clone();
+		if thread.parent.is_none() {
+			orphans.insert(thread.id);
+		}
+		(replace(&mut thread.handles, HandleTable::new()), orphans)
+	});
+	drop(handles);
+
+	// Neither the children nor a thread without a parent will be waited on
+	::interrupts::functions::without_interrupts(|| ORPHANED_THREADS.lock().extend(orphans));
+	reap_orphans();
+
+	with_active_thread(|thread| thread.state = ThreadState::Exited(exit_code));
+	yield_now();
+	unreachable!("Exited thread was resumed");
+}
+
+/// Frees the orphaned threads that have exited
+fn reap_orphans() {
+	let orphans: Vec<Thread> = ::interrupts::functions::without_interrupts(|| {
+		let mut orphaned_threads = ORPHANED_THREADS.lock();
+		let mut exited_threads = EXITED_THREADS.lock();
+		let exited: Vec<ThreadId> = orphaned_threads.iter()
+		                                            .filter(|orphan| exited_threads.contains_key(orphan))
+		                                            .cloned()
+		                                            .collect();
+		exited.iter().map(|orphan| {
+			orphaned_threads.remove(orphan);
+			exited_threads.remove(orphan).unwrap()
+		}).collect()
+	});
+
+	// The threads are dropped with interrupts enabled
+	// as freeing a large address space takes time
+	drop(orphans);
 }
 
 pub extern "C" fn context_switch(stack_pointer: usize) -> usize {
+	let _context = ::interrupts::functions::enter_interrupt();
+
+	// The timer may have been stopped for several ticks. See switch_thread
+	let tick_count = ::interrupts::pit_functions::on_interrupt();
+	TICKS.fetch_add(tick_count, Ordering::SeqCst);
+
+	// The scheduler decides when the time slice of the active thread
+	// has ended, but the idle thread is always switched away from and
+	// threads that have finished sleeping are given a chance to run
+	let woken = wake_sleeping_threads();
+	let preempt = match ACTIVE_THREAD.lock_direct().as_mut() {
+		Some(thread) => {
+			thread.statistics.running_ticks += tick_count;
+			if thread.is_idle() {
+				IDLE_TICKS.fetch_add(tick_count, Ordering::SeqCst);
+			}
+			thread.is_idle() || SCHEDULER.lock().tick(thread)
+		}
+		None => true,
+	};
+
+	let stack_pointer = match (preempt, woken) {
+		(true, _) => switch_thread(stack_pointer, SwitchReason::SliceEnded),
+		(false, true) => switch_thread(stack_pointer, SwitchReason::Preempted),
+		(false, false) => stack_pointer,
+	};
+	::interrupts::send_interrupt_end(false);
+	stack_pointer
+}
+
+pub extern "C" fn yield_switch(stack_pointer: usize) -> usize {
+	switch_thread(stack_pointer, SwitchReason::Yielded)
+}
+
+/// Why the active thread is switched away from
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SwitchReason {
+	/// The thread yielded, blocked or exited
+	Yielded,
+	/// The scheduler ended the time slice of the thread
+	SliceEnded,
+	/// A woken thread is given a chance to run before the slice ended
+	Preempted,
+}
+
+fn switch_thread(stack_pointer: usize, reason: SwitchReason) -> usize {
 	use core::ops::DerefMut;
 	use paging::PageLike;
 
-	let mut scheduler = SCHEDULER.lock();
+	// The globals are locked in the same order as everywhere else
 	let mut active_thread = ACTIVE_THREAD.lock_direct();
-	let mut active_table = ::paging::ACTIVE_PAGE_TABLE.lock();
+	let mut idle_thread = IDLE_THREAD.lock_direct();
+	let mut scheduler = SCHEDULER.lock();
+	let mut parked_threads = PARKED_THREADS.lock();
+	let mut exited_threads = EXITED_THREADS.lock();
 
 	// If this is our first context_switch, then there won't be
 	// an active thread
+	let ticks = ticks();
 	if let Some(mut thread) = active_thread.take() {
 		thread.stack_pointer = VirtualAddress::new(stack_pointer);
-		scheduler.schedule_new(thread);
+		match reason {
+			SwitchReason::Yielded => thread.statistics.voluntary_switches += 1,
+			_ => thread.statistics.preemptive_switches += 1,
+		}
+
+		if thread.state != ThreadState::Ready {
+			thread.statistics.blocked_since = Some(ticks);
+		}
+
+		match thread.state {
+			_ if thread.is_idle() => *idle_thread = Some(thread),
+			// A preempted thread has not used up or given up its slice
+			ThreadState::Ready if reason == SwitchReason::Preempted => scheduler.requeue(thread),
+			ThreadState::Ready => scheduler.schedule_new(thread),
+			ThreadState::Exited(_) => { exited_threads.insert(thread.id, thread); }
+			_ => parked_threads.push(thread),
+		}
 	}
 
-	let new_thread: Thread = scheduler.schedule_next().expect("Scheduler is empty");
-	::core::mem::replace(active_thread.deref_mut(), Some(new_thread.clone()));
+	// Blocked and sleeping threads are made ready by wake
+	let mut index = 0;
+	while index < parked_threads.len() {
+		let ready = match parked_threads[index].state {
+			ThreadState::Waiting(child) => exited_threads.contains_key(&child),
+			_ => false,
+		};
+
+		if ready {
+			let mut thread = parked_threads.remove(index);
+			thread.state = ThreadState::Ready;
+			scheduler.schedule_new(thread);
+		} else {
+			index += 1;
+		}
+	}
+
+	let mut new_thread: Thread = match scheduler.schedule_next() {
+		Some(thread) => thread,
+		None => idle_thread.take().expect("Idle thread is missing"),
+	};
+
+	// When only the idle thread can run, the timer is stopped until
+	// the next sleeping or throttled thread needs to be woken
+	let tick_count = match new_thread.is_idle() {
+		false => 1,
+		true => {
+			let wake_tick = SLEEPING_THREADS.lock().iter().next().map(|&(wake_tick, _)| wake_tick);
+			let next_tick = wake_tick.into_iter().chain(scheduler.next_release()).min();
+			next_tick.map_or(usize::max_value(), |tick| tick.saturating_sub(ticks))
+		}
+	};
+	::interrupts::pit_functions::set_tick_count(tick_count);
+
+	new_thread.statistics.scheduled_count += 1;
+	if let Some(since) = new_thread.statistics.blocked_since.take() {
+		new_thread.statistics.blocked_ticks += ticks - since;
+	}
+
+	let kernel_stack_end = new_thread.kernel_stack.end_address();
+	let new_stack_pointer = new_thread.stack_pointer.raw();
+	let fs_base = new_thread.fs_base;
+	let has_signal = new_thread.signals.has_deliverable();
+	let new_table = new_thread.page_table.clone();
+	ACTIVE_THREAD_ID.store(new_thread.id, Ordering::SeqCst);
+	::core::mem::replace(active_thread.deref_mut(), Some(new_thread));
 
 	// This stack is switched to whenever an interrupt occurs in user mode
 	// A kernel stack is needed to facilitate system calls and timer interrupts
-	let kernel_stack = ::x86_64::VirtualAddress(new_thread.kernel_stack.end_address().raw());
+	let kernel_stack = ::x86_64::VirtualAddress(kernel_stack_end.raw());
 	::interrupts::functions::TSS.lock().privilege_stack_table[0] = kernel_stack;
+	::system_call::functions::set_kernel_stack(kernel_stack_end.offset(1));
+	super::extended_state::on_switch();
+	write_fs_base(fs_base);
 
 	// Switching page tables invalidates the previous kernel stack so
 	// that's why we use a separate stack for handling the context switch
-	active_table.switch(new_thread.page_table);
+	// The table is locked last as the heap may need it to grow
+	::paging::ACTIVE_PAGE_TABLE.lock().switch(new_table);
 
-	::interrupts::send_interrupt_end(false);
-	new_thread.stack_pointer.raw()
+	// Signals are delivered by the thread itself as
+	// delivering them may fault on the user stack
+	match has_signal {
+		true => super::signal::redirect(new_stack_pointer),
+		false => new_stack_pointer,
+	}
 }
@@ -0,0 +1,106 @@
+use alloc::arc::Arc;
+use alloc::BTreeMap;
+use core::mem::size_of;
+use interrupts::functions::without_interrupts;
+use paging::user_access;
+use paging::user_access::UserAccessError;
+use paging::user_access::UserAccessResult;
+use paging::VirtualAddress;
+use spin::Mutex;
+use super::WaitQueue;
+
+// A futex lets user space threads wait for a word in memory to change
+// without spinning. Locks are taken in user space and the kernel is
+// only entered when a thread has to wait or there are waiters to wake.
+// Waiters are keyed by the physical address of the word, so threads
+// that map the same frame at different addresses share the futex
+
+// Queues are created when a thread first waits on an address
+// and removed once no thread uses them anymore
+static FUTEXES: Mutex<Option<BTreeMap<usize, Arc<WaitQueue>>>> = Mutex::new(None);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WaitResult {
+	Woken,
+	/// The word did not hold the expected value
+	ValueChanged,
+	TimedOut,
+	/// The active thread has a signal to deliver
+	Interrupted,
+}
+
+/// Parks the active thread if the word holds the expected value until
+/// it is woken by wake or the tick count reaches the timeout
+pub fn wait(address: &VirtualAddress, expected: u32, timeout: Option<usize>) -> UserAccessResult<WaitResult> {
+	let key = futex_key(address)?;
+	let queue = without_interrupts(|| {
+		FUTEXES.lock().get_or_insert_with(BTreeMap::new)
+		       .entry(key).or_insert_with(|| Arc::new(WaitQueue::new())).clone()
+	});
+
+	// The word is read again while registering so that a
+	// change and wake after the first read is not lost
+	let pointer = address.raw() as *const u32;
+	let mut value_changed = false;
+	let woken = queue.wait_once(|| {
+		value_changed = unsafe { ::core::ptr::read_volatile(pointer) } != expected;
+		!value_changed
+	}, timeout);
+
+	::core::mem::drop(queue);
+	remove_unused(key);
+	// Threads that are woken some other way return as if woken
+	let timed_out = timeout.map_or(false, |wake_tick| super::functions::ticks() >= wake_tick);
+	let interrupted = !woken && super::signal::is_interrupted();
+	Ok(match (woken, value_changed, timed_out, interrupted) {
+		(false, true, _, _) => WaitResult::ValueChanged,
+		(false, false, true, _) => WaitResult::TimedOut,
+		(false, false, false, true) => WaitResult::Interrupted,
+		_ => WaitResult::Woken,
+	})
+}
+
+/// Wakes up to count threads waiting on the word and
+/// returns how many were woken
+pub fn wake(address: &VirtualAddress, count: usize) -> UserAccessResult<usize> {
+	let key = futex_key(address)?;
+	let queue = without_interrupts(|| {
+		FUTEXES.lock().as_ref().and_then(|futexes| futexes.get(&key).cloned())
+	});
+
+	let woken = match queue {
+		Some(queue) => (0..count).take_while(|_| queue.wake_one()).count(),
+		None => 0,
+	};
+	remove_unused(key);
+	Ok(woken)
+}
+
+/// Returns the physical address of a writable word in user space
+fn futex_key(address: &VirtualAddress) -> UserAccessResult<usize> {
+	use core::ptr::{read_volatile, write_volatile};
+	user_access::validate_range(address, size_of::<u32>(), true)?;
+
+	// Copy on write pages are copied before the word is translated, as
+	// the word would otherwise move to another frame when it is written
+	without_interrupts(|| {
+		let pointer = address.raw() as *mut u32;
+		unsafe { write_volatile(pointer, read_volatile(pointer)); }
+		::paging::ACTIVE_PAGE_TABLE.lock().translate(address)
+		                                  .map(|address| address.raw() as usize)
+		                                  .ok_or(UserAccessError::NotAccessible)
+	})
+}
+
+fn remove_unused(key: usize) {
+	// Waiting threads hold a reference to their queue
+	without_interrupts(|| {
+		let mut futexes = FUTEXES.lock();
+		if let Some(futexes) = futexes.as_mut() {
+			let unused = futexes.get(&key).map_or(false, |queue| Arc::strong_count(queue) == 1);
+			if unused {
+				futexes.remove(&key);
+			}
+		}
+	})
+}
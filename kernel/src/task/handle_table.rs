@@ -0,0 +1,51 @@
+use alloc::boxed::Box;
+use alloc::BTreeMap;
+use graph::Resource;
+
+pub type Handle = u64;
+
+// Resources opened by a thread are referred to by user space
+// with an integer handle. Handles are never reused, so a stale
+// handle cannot refer to a resource opened later
+
+pub struct HandleTable {
+	resources: BTreeMap<Handle, Box<Resource + Send>>,
+	next_handle: Handle,
+}
+
+impl HandleTable {
+	pub fn new() -> HandleTable {
+		HandleTable {
+			resources: BTreeMap::new(),
+			next_handle: 0,
+		}
+	}
+
+	pub fn insert(&mut self, resource: Box<Resource + Send>) -> Handle {
+		let handle = self.next_handle;
+		self.next_handle += 1;
+		self.resources.insert(handle, resource);
+		handle
+	}
+
+	pub fn get_mut(&mut self, handle: Handle) -> Option<&mut Box<Resource + Send>> {
+		self.resources.get_mut(&handle)
+	}
+
+	pub fn remove(&mut self, handle: Handle) -> Option<Box<Resource + Send>> {
+		self.resources.remove(&handle)
+	}
+
+	/// Puts a removed resource back under its handle
+	pub fn restore(&mut self, handle: Handle, resource: Box<Resource + Send>) {
+		self.resources.insert(handle, resource);
+	}
+}
+
+impl Drop for HandleTable {
+	fn drop(&mut self) {
+		for resource in self.resources.values_mut() {
+			let _ = resource.close();
+		}
+	}
+}
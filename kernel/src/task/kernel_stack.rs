@@ -0,0 +1,87 @@
+use alloc::BTreeSet;
+use core::ops::DerefMut;
+use interrupts::functions::without_interrupts;
+use memory::FRAME_ALLOCATOR;
+use memory::FrameLikeAllocator;
+use paging::ACTIVE_PAGE_TABLE;
+use paging::EntryFlags;
+use paging::Page;
+use paging::PageIter;
+use paging::PageLike;
+use paging::VirtualAddress;
+use paging::reserved::KERNEL_STACK_SIZE;
+use paging::reserved::KERNEL_STACK_SLOT_COUNT;
+use paging::reserved::KERNEL_STACK_SLOT_SIZE;
+use paging::reserved::KERNEL_STACKS_BOTTOM;
+use utility::Global;
+
+// Kernel thread stacks are mapped in the kernel half so that they are
+// mapped in every address space. Each stack has its own slot of the
+// reserved region with an unmapped guard page below it
+
+static USED_SLOTS: Global<BTreeSet<usize>> = Global::new("KERNEL_STACK_SLOTS");
+
+pub fn initialize() {
+	USED_SLOTS.set(BTreeSet::new());
+}
+
+pub struct KernelStack {
+	slot: usize,
+}
+
+impl KernelStack {
+	/// Maps a stack in an unused slot
+	///
+	/// Returns None if every slot is used
+	pub fn new() -> Option<KernelStack> {
+		let slot = without_interrupts(|| {
+			let mut used_slots = USED_SLOTS.lock();
+			let slot = (0..KERNEL_STACK_SLOT_COUNT).find(|slot| !used_slots.contains(slot))?;
+			used_slots.insert(slot);
+			Some(slot)
+		})?;
+
+		// The pages are mapped before the stack is used, as a page
+		// fault while an interrupt frame is pushed cannot be handled
+		let stack = KernelStack { slot };
+		without_interrupts(|| {
+			let mut allocator = FRAME_ALLOCATOR.lock();
+			let mut active_table = ACTIVE_PAGE_TABLE.lock();
+			for page in stack.pages() {
+				let frame = allocator.allocate().expect("Out of memory: KERNEL_STACK_ALLOCATION");
+				active_table.map_to(page, frame, EntryFlags::WRITABLE, allocator.deref_mut());
+			}
+		});
+		Some(stack)
+	}
+
+	/// Returns the lowest address of the stack above the guard page
+	pub fn bottom(&self) -> VirtualAddress {
+		KERNEL_STACKS_BOTTOM.offset(self.slot * KERNEL_STACK_SLOT_SIZE + Page::SIZE as usize)
+	}
+
+	/// Returns the address just past the highest address of the stack
+	pub fn end(&self) -> usize {
+		self.bottom().raw() + KERNEL_STACK_SIZE
+	}
+
+	fn pages(&self) -> PageIter<Page> {
+		let start = Page::from_address(self.bottom());
+		let end = Page::from_address(VirtualAddress::new(self.end() - 1));
+		PageIter::inclusive(start, end)
+	}
+}
+
+impl Drop for KernelStack {
+	fn drop(&mut self) {
+		without_interrupts(|| {
+			let mut allocator = FRAME_ALLOCATOR.lock();
+			let mut active_table = ACTIVE_PAGE_TABLE.lock();
+			for page in self.pages() {
+				let frame = active_table.un_map(page, allocator.deref_mut());
+				allocator.deallocate(frame);
+			}
+		});
+		without_interrupts(|| USED_SLOTS.lock().remove(&self.slot));
+	}
+}
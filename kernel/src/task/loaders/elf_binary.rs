@@ -0,0 +1,284 @@
+use alloc::Vec;
+use paging::EntryFlags;
+use paging::InactivePageTable;
+use paging::VirtualAddress;
+use super::tls::TlsTemplate;
+use task::Thread;
+
+// An ELF64 executable starts with a fixed size header that
+// describes where the program headers are located. Each program
+// header of type PT_LOAD describes a segment that must be copied
+// into memory at its virtual address before the program runs
+// See https://wiki.osdev.org/ELF
+
+pub type ElfResult<T> = Result<T, ElfError>;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ElfError {
+	InvalidMagic,
+	UnsupportedFormat,
+	Truncated,
+	InvalidSegment,
+	KernelSegment,
+	OverlappingSegments,
+	NoLoadableSegments,
+}
+
+#[derive(Debug, Clone)]
+pub struct ElfHeader {
+	pub entry_point: u64,
+	pub program_header_offset: u64,
+	pub program_header_size: u16,
+	pub program_header_count: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProgramHeader {
+	pub kind: u32,
+	pub flags: u32,
+	pub offset: u64,
+	pub virtual_address: u64,
+	pub file_size: u64,
+	pub memory_size: u64,
+	pub alignment: u64,
+}
+
+impl ProgramHeader {
+	pub const LOAD: u32 = 1;
+	pub const TLS: u32 = 7;
+
+	pub const EXECUTABLE: u32 = 1 << 0;
+	pub const WRITABLE: u32 = 1 << 1;
+
+	pub fn entry_flags(&self) -> EntryFlags {
+		let mut flags = EntryFlags::USER_ACCESSIBLE;
+		if self.flags & Self::WRITABLE != 0 {
+			flags |= EntryFlags::WRITABLE;
+		}
+
+		if self.flags & Self::EXECUTABLE == 0 {
+			flags |= EntryFlags::NO_EXECUTE;
+		}
+		flags
+	}
+
+	pub fn end_address(&self) -> u64 {
+		self.virtual_address + self.memory_size
+	}
+}
+
+pub const MAGIC: &[u8] = b"\x7fELF";
+
+const HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+pub fn load_elf_binary(binary: &[u8], mut base_table: InactivePageTable) -> ElfResult<Thread> {
+	let (entry_point, thread_pointer) = map_elf_binary(binary, &mut base_table)?;
+	Ok(super::functions::create_thread(base_table, entry_point, thread_pointer, &[], &[]))
+}
+
+/// Maps the segments and thread local storage of the binary and
+/// returns its entry point and initial thread pointer
+pub fn map_elf_binary(binary: &[u8], table: &mut InactivePageTable) -> ElfResult<(VirtualAddress, VirtualAddress)> {
+	let header = parse_header(binary)?;
+	let segments = parse_segments(binary, &header)?;
+	let template = parse_tls(binary, &header)?;
+
+	// Every segment is validated before anything is mapped so that
+	// a malformed binary does not leave the table half populated
+	for segment in &segments {
+		map_segment(binary, segment, table);
+	}
+
+	let thread_pointer = super::tls::map_tls(&template, table);
+	Ok((VirtualAddress::new(header.entry_point as usize), thread_pointer))
+}
+
+pub fn parse_header(binary: &[u8]) -> ElfResult<ElfHeader> {
+	const CLASS_64: u8 = 2;
+	const LITTLE_ENDIAN: u8 = 1;
+	const CURRENT_VERSION: u8 = 1;
+	const TYPE_EXECUTABLE: u64 = 2;
+	const MACHINE_X86_64: u64 = 0x3e;
+
+	if binary.len() < HEADER_SIZE {
+		return Err(ElfError::Truncated);
+	}
+
+	if &binary[0..4] != MAGIC {
+		return Err(ElfError::InvalidMagic);
+	}
+
+	// Position independent executables (ET_DYN) would need to be
+	// relocated, so only static executables are accepted
+	if binary[4] != CLASS_64 || binary[5] != LITTLE_ENDIAN || binary[6] != CURRENT_VERSION ||
+		read_value(binary, 16, 2)? != TYPE_EXECUTABLE || read_value(binary, 18, 2)? != MACHINE_X86_64 {
+		return Err(ElfError::UnsupportedFormat);
+	}
+
+	let header = ElfHeader {
+		entry_point: read_value(binary, 24, 8)?,
+		program_header_offset: read_value(binary, 32, 8)?,
+		program_header_size: read_value(binary, 54, 2)? as u16,
+		program_header_count: read_value(binary, 56, 2)? as u16,
+	};
+
+	if (header.program_header_size as usize) < PROGRAM_HEADER_SIZE {
+		return Err(ElfError::UnsupportedFormat);
+	}
+
+	if header.entry_point > ::paging::reserved::USER_SPACE_TOP.raw() as u64 {
+		return Err(ElfError::KernelSegment);
+	}
+	Ok(header)
+}
+
+/// Returns the validated PT_LOAD segments of the binary
+pub fn parse_segments(binary: &[u8], header: &ElfHeader) -> ElfResult<Vec<ProgramHeader>> {
+	let mut segments: Vec<ProgramHeader> = Vec::new();
+	for index in 0..header.program_header_count as u64 {
+		let offset = (header.program_header_size as u64).checked_mul(index)
+		                                                .and_then(|offset| offset.checked_add(header.program_header_offset))
+		                                                .ok_or(ElfError::Truncated)?;
+		let segment = parse_program_header(binary, offset as usize)?;
+		if segment.kind != ProgramHeader::LOAD || segment.memory_size == 0 {
+			continue;
+		}
+
+		validate_segment(binary, &segment)?;
+
+		// Segments are mapped with whole pages, so two segments
+		// sharing a page would attempt to map the same page twice
+		let (start_page, end_page) = page_range(&segment);
+		for other in &segments {
+			let (other_start, other_end) = page_range(other);
+			if start_page <= other_end && other_start <= end_page {
+				return Err(ElfError::OverlappingSegments);
+			}
+		}
+		segments.push(segment);
+	}
+
+	if segments.is_empty() {
+		return Err(ElfError::NoLoadableSegments);
+	}
+	Ok(segments)
+}
+
+/// Returns the initialization template of the PT_TLS segment or
+/// an empty template if the binary has no thread local variables
+pub fn parse_tls(binary: &[u8], header: &ElfHeader) -> ElfResult<TlsTemplate> {
+	let mut template = None;
+	for index in 0..header.program_header_count as u64 {
+		let offset = (header.program_header_size as u64).checked_mul(index)
+		                                                .and_then(|offset| offset.checked_add(header.program_header_offset))
+		                                                .ok_or(ElfError::Truncated)?;
+		let segment = parse_program_header(binary, offset as usize)?;
+		if segment.kind != ProgramHeader::TLS {
+			continue;
+		}
+
+		if template.is_some() || segment.file_size > segment.memory_size {
+			return Err(ElfError::InvalidSegment);
+		}
+
+		let file_end = segment.offset.checked_add(segment.file_size).ok_or(ElfError::Truncated)?;
+		if file_end > binary.len() as u64 {
+			return Err(ElfError::Truncated);
+		}
+
+		// The block and the padding needed to align it must
+		// fit into the TLS region, see paging/reserved
+		let alignment = segment.alignment.max(1);
+		let padded_size = segment.memory_size.saturating_add(alignment.saturating_mul(2));
+		if !alignment.is_power_of_two() || padded_size > super::tls::MAX_TLS_SIZE as u64 {
+			return Err(ElfError::InvalidSegment);
+		}
+
+		template = Some(TlsTemplate {
+			image: binary[segment.offset as usize..file_end as usize].to_vec(),
+			memory_size: segment.memory_size as usize,
+			alignment: alignment as usize,
+		});
+	}
+	Ok(template.unwrap_or_else(TlsTemplate::empty))
+}
+
+fn parse_program_header(binary: &[u8], offset: usize) -> ElfResult<ProgramHeader> {
+	let field = |field_offset: usize, size: usize| -> ElfResult<u64> {
+		read_value(binary, offset.checked_add(field_offset).ok_or(ElfError::Truncated)?, size)
+	};
+
+	Ok(ProgramHeader {
+		kind: field(0, 4)? as u32,
+		flags: field(4, 4)? as u32,
+		offset: field(8, 8)?,
+		virtual_address: field(16, 8)?,
+		file_size: field(32, 8)?,
+		memory_size: field(40, 8)?,
+		alignment: field(48, 8)?,
+	})
+}
+
+fn validate_segment(binary: &[u8], segment: &ProgramHeader) -> ElfResult<()> {
+	if segment.file_size > segment.memory_size {
+		return Err(ElfError::InvalidSegment);
+	}
+
+	let file_end = segment.offset.checked_add(segment.file_size).ok_or(ElfError::Truncated)?;
+	if file_end > binary.len() as u64 {
+		return Err(ElfError::Truncated);
+	}
+
+	// The top of user space is reserved for the stack and thread local storage
+	let memory_end = segment.virtual_address.checked_add(segment.memory_size).ok_or(ElfError::KernelSegment)?;
+	if memory_end > ::paging::reserved::USER_TLS_BOTTOM.raw() as u64 {
+		return Err(ElfError::KernelSegment);
+	}
+	Ok(())
+}
+
+fn page_range(segment: &ProgramHeader) -> (u64, u64) {
+	use paging::Page;
+	use paging::PageLike;
+	let start = segment.virtual_address / Page::SIZE;
+	let end = (segment.end_address() - 1) / Page::SIZE;
+	(start, end)
+}
+
+fn map_segment(binary: &[u8], segment: &ProgramHeader, table: &mut InactivePageTable) {
+	use paging::Page;
+	use paging::PageIter;
+	use paging::PageLike;
+	use super::functions;
+
+	let start = VirtualAddress::new(segment.virtual_address as usize);
+	let end = VirtualAddress::new(segment.end_address() as usize - 1);
+	let start_page = Page::from_address(start.clone());
+	let end_page = Page::from_address(end);
+	functions::allocate_region(PageIter::inclusive(start_page.clone(), end_page.clone()),
+	                           table, segment.entry_flags());
+
+	// Frames are not cleared when they are allocated, so everything
+	// on the segment's pages that does not come from the file has to
+	// be zeroed. This includes the .bss section at the segment's end
+	let leading_size = start.raw() - start_page.start_address().raw();
+	functions::zero_data(leading_size, table, start_page.start_address());
+
+	let file_start = segment.offset as usize;
+	let file_end = file_start + segment.file_size as usize;
+	if file_start != file_end {
+		functions::write_data(&binary[file_start..file_end], table, start.clone());
+	}
+
+	let zero_start = start.offset(segment.file_size as usize);
+	let zero_size = end_page.end_address().raw() - zero_start.raw() + 1;
+	functions::zero_data(zero_size, table, zero_start);
+}
+
+/// Reads a little endian value of up to eight bytes
+fn read_value(binary: &[u8], offset: usize, size: usize) -> ElfResult<u64> {
+	let end = offset.checked_add(size).ok_or(ElfError::Truncated)?;
+	let bytes = binary.get(offset..end).ok_or(ElfError::Truncated)?;
+	Ok(bytes.iter().rev().fold(0, |value, byte| (value << 8) | *byte as u64))
+}
@@ -2,31 +2,27 @@ use paging::InactivePageTable;
 use paging::VirtualAddress;
 use task::Thread;
 
+// Flat binaries that are loaded by path (see loaders/functions::load_binary)
+// are mapped here and start executing at their first byte, so they should
+// be assembled with this origin
+pub const FLAT_BINARY_BASE: VirtualAddress = VirtualAddress::new(0x40_0000);
+
 pub fn load_flat_binary(binary: &[u8], mut base_table: InactivePageTable, entry_point: VirtualAddress) -> Thread {
 	use super::functions;
-	use paging::EntryFlags;
 
 	// First we copy the binary into memory and map it at the start
 	// of the virtual address space
 	// Warning: Keep in mind that executing code at address 0 will
 	// cause a General Protection Fault so do not set your
 	// entry point to be at address 0 (null)
-	functions::map_data(binary, &mut base_table, VirtualAddress::new(0),
-	                    EntryFlags::USER_ACCESSIBLE);
-
-	// We create stacks at intervals of sixteen pages, so here
-	// we calculate the next stack location
-	let last_address = binary.len() - 1;
-	let stack_bottom = ::utility::math::align_up_usize(last_address, super::stack::STACK_SIZE as usize);
-	let stack_bottom = VirtualAddress::new(stack_bottom);
-	let (kernel_stack, stack_pointer) = super::stack::create_local_stack(stack_bottom, &mut base_table);
-
-	let stack_data = super::stack::create_initial_stack(&entry_point, &stack_pointer);
-	functions::write_data(::utility::convert::as_u8_slice(&stack_data), &mut base_table, stack_pointer.clone());
+	map_flat_binary(binary, &mut base_table, VirtualAddress::new(0));
+	let thread_pointer = super::tls::map_tls(&super::tls::TlsTemplate::empty(), &mut base_table);
+	functions::create_thread(base_table, entry_point, thread_pointer, &[], &[])
+}
 
-	Thread {
-		page_table: base_table,
-		kernel_stack,
-		stack_pointer,
-	}
+pub fn map_flat_binary(binary: &[u8], table: &mut InactivePageTable, base: VirtualAddress) {
+	use paging::EntryFlags;
+	assert!(base.raw() + binary.len() <= ::paging::reserved::USER_TLS_BOTTOM.raw(),
+	        "Flat binary overlaps the user stack");
+	super::functions::map_data(binary, table, base, EntryFlags::USER_ACCESSIBLE);
 }
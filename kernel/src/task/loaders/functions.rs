@@ -1,4 +1,6 @@
+use alloc::String;
 use core::ops::DerefMut;
+use interrupts::functions::without_interrupts;
 use memory::Frame;
 use memory::FRAME_ALLOCATOR;
 use memory::FrameLike;
@@ -11,6 +13,94 @@ use paging::Page;
 use paging::PageIter;
 use paging::PageLike;
 use paging::VirtualAddress;
+use super::elf_binary::ElfError;
+use super::elf_binary::ElfResult;
+use task::Thread;
+
+/// Loads an ELF binary or, if the binary does not start with the
+/// ELF magic number, a flat binary at flat_binary::FLAT_BINARY_BASE
+pub fn load_binary(binary: &[u8], mut table: InactivePageTable, arguments: &[String],
+                   environment: &[String]) -> ElfResult<Thread> {
+	match map_binary(binary, &mut table) {
+		Ok((entry_point, thread_pointer)) => Ok(create_thread(table, entry_point, thread_pointer,
+		                                                      arguments, environment)),
+		Err(error) => {
+			::paging::teardown::free_table(table);
+			Err(error)
+		}
+	}
+}
+
+/// Checks that the binary can be mapped by map_binary
+pub fn validate_binary(binary: &[u8]) -> ElfResult<()> {
+	use super::elf_binary;
+	if is_elf_binary(binary) {
+		let header = elf_binary::parse_header(binary)?;
+		elf_binary::parse_segments(binary, &header)?;
+		elf_binary::parse_tls(binary, &header)?;
+		return Ok(());
+	}
+
+	if binary.is_empty() {
+		return Err(ElfError::Truncated);
+	}
+
+	let base = super::flat_binary::FLAT_BINARY_BASE.raw();
+	if base + binary.len() > ::paging::reserved::USER_TLS_BOTTOM.raw() {
+		return Err(ElfError::KernelSegment);
+	}
+	Ok(())
+}
+
+/// Maps the binary and its thread local storage into the table and
+/// returns its entry point and initial thread pointer
+pub fn map_binary(binary: &[u8], table: &mut InactivePageTable) -> ElfResult<(VirtualAddress, VirtualAddress)> {
+	use super::flat_binary::FLAT_BINARY_BASE;
+	use super::tls::TlsTemplate;
+	validate_binary(binary)?;
+	if is_elf_binary(binary) {
+		return super::elf_binary::map_elf_binary(binary, table);
+	}
+
+	super::flat_binary::map_flat_binary(binary, table, FLAT_BINARY_BASE);
+	let thread_pointer = super::tls::map_tls(&TlsTemplate::empty(), table);
+	Ok((FLAT_BINARY_BASE, thread_pointer))
+}
+
+pub fn is_elf_binary(binary: &[u8]) -> bool {
+	binary.starts_with(super::elf_binary::MAGIC)
+}
+
+/// Creates the stacks of a thread that starts at the entry point
+pub fn create_thread(mut table: InactivePageTable, entry_point: VirtualAddress, thread_pointer: VirtualAddress,
+                     arguments: &[String], environment: &[String]) -> Thread {
+	use super::stack;
+
+	// Every thread has its stack at the top of user space
+	let stack_bottom = ::paging::reserved::USER_STACK_BOTTOM;
+	let (kernel_stack, stack_top) = stack::create_local_stack(stack_bottom, &mut table);
+	let stack_pointer = write_arguments(&mut table, &stack_top, &entry_point, arguments, environment);
+
+	let stack_data = stack::create_initial_stack(&entry_point, &stack_pointer);
+	write_data(::utility::convert::as_u8_slice(&stack_data), &mut table, stack_top.clone());
+	let mut thread = Thread::new(table, kernel_stack, stack_top);
+	thread.fs_base = thread_pointer.raw() as u64;
+	thread
+}
+
+/// Writes the arguments, environment and auxiliary vector below
+/// stack_top and returns the initial user stack pointer
+pub fn write_arguments(table: &mut InactivePageTable, stack_top: &VirtualAddress, entry_point: &VirtualAddress,
+                       arguments: &[String], environment: &[String]) -> VirtualAddress {
+	use super::stack;
+	let auxiliary = [
+		(stack::AT_PAGESZ, Page::SIZE),
+		(stack::AT_ENTRY, entry_point.raw() as u64),
+	];
+	let (stack_pointer, data) = stack::create_argument_stack(stack_top, arguments, environment, &auxiliary);
+	write_data(&data, table, stack_pointer.clone());
+	stack_pointer
+}
 
 pub fn map_data(data: &[u8], table: &mut InactivePageTable, offset: VirtualAddress, flags: EntryFlags) {
 	let end_address = offset.offset(data.len() - 1);
@@ -23,44 +113,62 @@ pub fn map_data(data: &[u8], table: &mut InactivePageTable, offset: VirtualAddre
 /// Maps a region of the inactive page table's virtual address space
 /// into physical memory
 pub fn allocate_region(region: PageIter<Page>, table: &mut InactivePageTable, flags: EntryFlags) {
-	let mut allocator = FRAME_ALLOCATOR.lock();
-	let allocator = allocator.deref_mut();
-	for page in region {
-		let frame = allocator.allocate().expect("Out of memory: REGION_ALLOCATION");
-		ACTIVE_PAGE_TABLE.lock().with(table, allocator, |table, allocator| {
-			table.map_to(page, frame, flags.clone(), allocator);
-		});
-	}
+	// Programs are also loaded from system calls, so the tables
+	// must be locked with interrupts disabled
+	without_interrupts(|| {
+		let mut allocator = FRAME_ALLOCATOR.lock();
+		let allocator = allocator.deref_mut();
+		for page in region {
+			let frame = allocator.allocate().expect("Out of memory: REGION_ALLOCATION");
+			ACTIVE_PAGE_TABLE.lock().with(table, allocator, |table, allocator| {
+				table.map_to(page, frame, flags.clone(), allocator);
+			});
+		}
+	})
 }
 
 /// Writes an array of bytes at a virtual address in the inactive table's
 /// virtual address space
 pub fn write_data(data: &[u8], table: &mut InactivePageTable, offset: VirtualAddress) {
-	let staging_page = Page::from_address(::paging::reserved::TEMPORARY_PAGE);
-	let mut active_table = ACTIVE_PAGE_TABLE.lock();
-	let mut allocator = FRAME_ALLOCATOR.lock();
-	let allocator = allocator.deref_mut();
-
-	let mut map_frame = |active_table: &mut ActivePageTable, allocator: &mut FrameLikeAllocator<Frame>,
-	                     address: &VirtualAddress| {
-		let frame = active_table.with(table, allocator, |table, _| {
-			Frame::from_address(table.translate(address).expect("Data destination not mapped"))
-		});
-		active_table.map_to(staging_page.clone(), frame, EntryFlags::WRITABLE, allocator);
-	};
-
-	map_frame(&mut active_table, allocator, &offset);
-	for (index, byte) in data.iter().enumerate() {
-		let destination_address = offset.offset(index);
-		let on_page_boundary = destination_address.raw() as u64 % Page::SIZE == 0;
-		if on_page_boundary && index != 0 {
-			active_table.discard(staging_page.clone(), allocator);
-			map_frame(&mut active_table, allocator, &destination_address);
+	without_interrupts(|| {
+		let staging_page = Page::from_address(::paging::reserved::TEMPORARY_PAGE);
+		let mut active_table = ACTIVE_PAGE_TABLE.lock();
+		let mut allocator = FRAME_ALLOCATOR.lock();
+		let allocator = allocator.deref_mut();
+
+		let mut map_frame = |active_table: &mut ActivePageTable, allocator: &mut FrameLikeAllocator<Frame>,
+		                     address: &VirtualAddress| {
+			let frame = active_table.with(table, allocator, |table, _| {
+				Frame::from_address(table.translate(address).expect("Data destination not mapped"))
+			});
+			active_table.map_to(staging_page.clone(), frame, EntryFlags::WRITABLE, allocator);
+		};
+
+		map_frame(&mut active_table, allocator, &offset);
+		for (index, byte) in data.iter().enumerate() {
+			let destination_address = offset.offset(index);
+			let on_page_boundary = destination_address.raw() as u64 % Page::SIZE == 0;
+			if on_page_boundary && index != 0 {
+				active_table.discard(staging_page.clone(), allocator);
+				map_frame(&mut active_table, allocator, &destination_address);
+			}
+
+			let byte_index = (destination_address.raw() as u64 % Page::SIZE) as usize;
+			let destination_byte = (staging_page.start_address().raw() + byte_index) as *mut u8;
+			unsafe { *destination_byte = *byte; }
 		}
+		active_table.discard(staging_page.clone(), allocator);
+	})
+}
 
-		let byte_index = (destination_address.raw() as u64 % Page::SIZE) as usize;
-		let destination_byte = (staging_page.start_address().raw() + byte_index) as *mut u8;
-		unsafe { *destination_byte = *byte; }
+/// Writes zeroes over a region of the inactive table's virtual
+/// address space
+pub fn zero_data(size: usize, table: &mut InactivePageTable, offset: VirtualAddress) {
+	const ZEROES: [u8; Page::SIZE as usize] = [0; Page::SIZE as usize];
+	let mut written = 0;
+	while written < size {
+		let length = (size - written).min(ZEROES.len());
+		write_data(&ZEROES[..length], table, offset.offset(written));
+		written += length;
 	}
-	active_table.discard(staging_page.clone(), allocator);
 }
@@ -1,3 +1,5 @@
 pub mod flat_binary;
+pub mod elf_binary;
 pub mod functions;
-pub mod stack;
\ No newline at end of file
+pub mod stack;
+pub mod tls;
\ No newline at end of file
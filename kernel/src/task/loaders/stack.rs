@@ -1,19 +1,28 @@
+use alloc::String;
+use alloc::Vec;
 use paging::InactivePageTable;
 use paging::Page;
 use paging::PageLike;
 use paging::VirtualAddress;
 
-// The compiler adds different numbers to the rsp
-// depending on the build mode
-#[cfg(debug_assertions)]
-pub const PADDING_COUNT: usize = 16;
-#[cfg(not(debug_assertions))]
-pub const PADDING_COUNT: usize = 15;
+// The timer and yield handlers push every general purpose register
+// below the exception stack frame, so a saved thread stack has the
+// layout of a SystemCallFrame. See interrupts/handlers::timer_handler
+pub const REGISTER_COUNT: usize = 15;
 
 pub const EXCEPTION_FRAME_SIZE: usize = 5;
-pub const INITIAL_STACK_SIZE: usize = EXCEPTION_FRAME_SIZE + PADDING_COUNT;
+pub const INITIAL_STACK_SIZE: usize = EXCEPTION_FRAME_SIZE + REGISTER_COUNT;
 
-pub const STACK_SIZE: u64 = 16 * Page::SIZE;
+pub const STACK_SIZE: u64 = ::paging::reserved::USER_STACK_SIZE as u64;
+
+// Auxiliary vector entry types from the System V ABI
+pub const AT_NULL: u64 = 0;
+pub const AT_PAGESZ: u64 = 6;
+pub const AT_ENTRY: u64 = 9;
+
+// The strings passed to a program must leave room on the user stack
+pub const MAX_ARGUMENT_COUNT: usize = 256;
+pub const MAX_ARGUMENT_SIZE: usize = 8 * Page::SIZE as usize;
 
 pub fn create_initial_stack(entry_point: &VirtualAddress, stack_pointer: &VirtualAddress)
                             -> [u64; INITIAL_STACK_SIZE] {
@@ -26,11 +35,26 @@ pub fn create_initial_stack(entry_point: &VirtualAddress, stack_pointer: &Virtua
 	// Interrupt Enable Flag, Reserved
 	const R_FLAGS: u64 = 0b10_0000_0010;
 	let mut stack = [0; INITIAL_STACK_SIZE];
-	stack[PADDING_COUNT] = entry_point.raw() as u64;
-	stack[PADDING_COUNT + 1] = *::interrupts::functions::USER_CODE_SELECTOR.try().unwrap() as u64;
-	stack[PADDING_COUNT + 2] = R_FLAGS;
-	stack[PADDING_COUNT + 3] = stack_pointer.raw() as u64;
-	stack[PADDING_COUNT + 4] = *::interrupts::functions::USER_DATA_SELECTOR.try().unwrap() as u64;
+	stack[REGISTER_COUNT] = entry_point.raw() as u64;
+	stack[REGISTER_COUNT + 1] = *::interrupts::functions::USER_CODE_SELECTOR.try().unwrap() as u64;
+	stack[REGISTER_COUNT + 2] = R_FLAGS;
+	stack[REGISTER_COUNT + 3] = stack_pointer.raw() as u64;
+	stack[REGISTER_COUNT + 4] = *::interrupts::functions::USER_DATA_SELECTOR.try().unwrap() as u64;
+	stack
+}
+
+/// Creates a stack like create_initial_stack that resumes in kernel mode
+pub fn create_kernel_stack(instruction_pointer: &VirtualAddress, stack_pointer: &VirtualAddress)
+                           -> [u64; INITIAL_STACK_SIZE] {
+	// Reserved, interrupts are disabled until the code
+	// at the instruction pointer enables them
+	const R_FLAGS: u64 = 0b10;
+	let mut stack = [0; INITIAL_STACK_SIZE];
+	stack[REGISTER_COUNT] = instruction_pointer.raw() as u64;
+	stack[REGISTER_COUNT + 1] = *::interrupts::functions::KERNEL_CODE_SELECTOR.try().unwrap() as u64;
+	stack[REGISTER_COUNT + 2] = R_FLAGS;
+	stack[REGISTER_COUNT + 3] = stack_pointer.raw() as u64;
+	stack[REGISTER_COUNT + 4] = *::interrupts::functions::KERNEL_DATA_SELECTOR.try().unwrap() as u64;
 	stack
 }
 
@@ -47,10 +71,61 @@ pub fn create_local_stack(stack_bottom: VirtualAddress, table: &mut InactivePage
 	super::functions::allocate_region(PageIter::inclusive(kernel_stack_page.clone(), kernel_stack_page.clone()),
 	                                  table, EntryFlags::WRITABLE);
 
-	let stack_top = VirtualAddress::new(kernel_stack_top.raw() - Page::SIZE as usize);
+	(kernel_stack_page, create_user_stack(stack_bottom, table))
+}
+
+/// Maps the user mode part of a stack created by create_local_stack
+pub fn create_user_stack(stack_bottom: VirtualAddress, table: &mut InactivePageTable) -> VirtualAddress {
+	use paging::PageIter;
+	use paging::EntryFlags;
+
+	// The top page of the stack belongs to the kernel stack
+	let stack_top = VirtualAddress::new(stack_bottom.raw() + (STACK_SIZE - Page::SIZE) as usize - 1);
 	let stack_bottom_page = Page::from_address(stack_bottom);
 	let stack_top_page = Page::from_address(stack_top.clone());
 	super::functions::allocate_region(PageIter::inclusive(stack_bottom_page, stack_top_page),
 	                                  table, EntryFlags::USER_ACCESSIBLE | EntryFlags::WRITABLE);
-	(kernel_stack_page, stack_top)
-}
\ No newline at end of file
+	stack_top
+}
+
+/// Lays out argc, argv, envp and the auxiliary vector below stack_top
+/// in the System V x86_64 style. Returns the stack pointer, which
+/// points at argc, and the data to be written there
+pub fn create_argument_stack(stack_top: &VirtualAddress, arguments: &[String], environment: &[String],
+                             auxiliary: &[(u64, u64)]) -> (VirtualAddress, Vec<u8>) {
+	use core::mem::size_of;
+
+	// The null terminated strings are placed at the top
+	let mut strings = Vec::new();
+	let mut string_offsets = Vec::new();
+	for string in arguments.iter().chain(environment.iter()) {
+		string_offsets.push(strings.len());
+		strings.extend_from_slice(string.as_bytes());
+		strings.push(0);
+	}
+	let strings_bottom = stack_top.raw() - strings.len();
+	let string_pointer = |index: usize| (strings_bottom + string_offsets[index]) as u64;
+
+	// Below them are argc, the argument pointers, the environment
+	// pointers and the auxiliary vector, each terminated by zero
+	let mut words = Vec::new();
+	words.push(arguments.len() as u64);
+	words.extend((0..arguments.len()).map(&string_pointer));
+	words.push(0);
+	words.extend((arguments.len()..string_offsets.len()).map(&string_pointer));
+	words.push(0);
+	for &(key, value) in auxiliary.iter() {
+		words.push(key);
+		words.push(value);
+	}
+	words.push(AT_NULL);
+	words.push(0);
+
+	// The ABI requires the stack pointer to be 16 byte aligned
+	let stack_pointer = (strings_bottom - words.len() * size_of::<u64>()) & !0xf;
+	let mut data = Vec::with_capacity(stack_top.raw() - stack_pointer);
+	data.extend_from_slice(::utility::convert::as_u8_slice(&words));
+	data.resize(strings_bottom - stack_pointer, 0);
+	data.extend_from_slice(&strings);
+	(VirtualAddress::new(stack_pointer), data)
+}
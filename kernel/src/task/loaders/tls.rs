@@ -0,0 +1,71 @@
+use alloc::Vec;
+use paging::InactivePageTable;
+use paging::VirtualAddress;
+
+// Thread local storage uses the x86_64 variant II layout. The TLS
+// block holds the initialized data of the PT_TLS segment followed by
+// zeroes and ends at the thread pointer, which the fs base register
+// points to. The thread control block starts at the thread pointer
+// and its first word points to itself, so that programs can read the
+// thread pointer with mov rax, fs:0
+// See https://wiki.osdev.org/Thread_Local_Storage
+
+// The stack protector of many compilers reads its canary from fs:0x28
+pub const CONTROL_BLOCK_SIZE: usize = 64;
+pub const MAX_TLS_SIZE: usize = ::paging::reserved::USER_TLS_SIZE - CONTROL_BLOCK_SIZE;
+
+#[derive(Debug, Clone)]
+pub struct TlsTemplate {
+	/// The initialized part of the block
+	pub image: Vec<u8>,
+	/// The size of the block including the zeroed part
+	pub memory_size: usize,
+	pub alignment: usize,
+}
+
+impl TlsTemplate {
+	/// A template for programs without thread local variables,
+	/// which still get a thread control block
+	pub fn empty() -> TlsTemplate {
+		TlsTemplate {
+			image: Vec::new(),
+			memory_size: 0,
+			alignment: 1,
+		}
+	}
+}
+
+/// Lays out the TLS block and thread control block so that they end
+/// before region_end. Returns the thread pointer, the start address
+/// of the data and the data
+pub fn create_tls_block(template: &TlsTemplate, region_end: usize) -> (VirtualAddress, VirtualAddress, Vec<u8>) {
+	use utility::math::align_up_u64;
+	// Programs address the data at the thread pointer minus the size
+	// rounded up to the segment alignment, so only the thread pointer
+	// is aligned to at least sixteen bytes for the control block
+	let alignment = template.alignment.max(1);
+	let thread_pointer = (region_end - CONTROL_BLOCK_SIZE) & !(alignment.max(16) - 1);
+	let block_size = align_up_u64(template.memory_size as u64, alignment as u64) as usize;
+	let block_start = thread_pointer - block_size;
+
+	let mut data = Vec::with_capacity(block_size + CONTROL_BLOCK_SIZE);
+	data.extend_from_slice(&template.image);
+	data.resize(block_size, 0);
+	data.extend_from_slice(::utility::convert::as_u8_slice(&[thread_pointer as u64]));
+	data.resize(block_size + CONTROL_BLOCK_SIZE, 0);
+	(VirtualAddress::new(thread_pointer), VirtualAddress::new(block_start), data)
+}
+
+/// Maps the TLS region of the table and returns the thread pointer
+pub fn map_tls(template: &TlsTemplate, table: &mut InactivePageTable) -> VirtualAddress {
+	use paging::EntryFlags;
+	use paging::reserved::USER_TLS_BOTTOM;
+	use paging::reserved::USER_TLS_SIZE;
+	use super::functions;
+
+	let region_end = USER_TLS_BOTTOM.raw() + USER_TLS_SIZE;
+	let (thread_pointer, block_start, data) = create_tls_block(template, region_end);
+	functions::map_data(&data, table, block_start, EntryFlags::USER_ACCESSIBLE | EntryFlags::WRITABLE |
+	                    EntryFlags::NO_EXECUTE);
+	thread_pointer
+}
@@ -1,10 +1,24 @@
 pub use self::functions::SCHEDULER;
+pub use self::handle_table::Handle;
+pub use self::handle_table::HandleTable;
 pub use self::scheduler::Scheduler;
+pub use self::thread::Priority;
 pub use self::thread::Thread;
+pub use self::thread::ThreadId;
+pub use self::thread::ThreadState;
+pub use self::thread::ThreadStatistics;
+pub use self::wait_queue::WaitQueue;
 
 pub mod scheduler;
 pub mod schedulers;
 pub mod thread;
+pub mod handle_table;
+pub mod extended_state;
+pub mod kernel_stack;
+pub mod wait_queue;
+pub mod futex;
+pub mod sync;
+pub mod signal;
 pub mod functions;
 pub mod loaders;
 
@@ -1,4 +1,5 @@
 use super::Thread;
+use super::ThreadId;
 
 pub trait Scheduler {
 	/// Halts the interrupted thread and selects the next thread to run
@@ -6,4 +7,33 @@ pub trait Scheduler {
 
 	/// Adds a new thread to a pool of threads to be executed
 	fn schedule_new(&mut self, new_thread: Thread);
+
+	/// Adds a thread that was removed or preempted without treating it
+	/// as though it had blocked, yielded or used up its time slice
+	fn requeue(&mut self, thread: Thread) {
+		self.schedule_new(thread);
+	}
+
+	/// Removes a thread that is waiting to be selected
+	fn remove(&mut self, id: ThreadId) -> Option<Thread>;
+
+	/// Calls the function with a thread that is waiting to be selected
+	/// without moving it. The function must not change anything that
+	/// the thread is ordered by. Returns false if there is no such thread
+	fn with_thread(&mut self, id: ThreadId, function: &mut FnMut(&mut Thread)) -> bool;
+
+	/// Calls the function with every thread waiting to be selected
+	fn for_each(&self, function: &mut FnMut(&Thread));
+
+	/// Called on every timer interrupt with the active thread
+	/// Returns true if the active thread should be preempted
+	fn tick(&mut self, _active_thread: &mut Thread) -> bool {
+		true
+	}
+
+	/// The tick at which a thread that is not ready becomes ready
+	/// The timer is not stopped past this tick when idle
+	fn next_release(&self) -> Option<usize> {
+		None
+	}
 }
@@ -0,0 +1,205 @@
+use alloc::boxed::Box;
+use alloc::Vec;
+use super::Scheduler;
+use super::Thread;
+use super::ThreadId;
+
+// The earliest deadline first scheduling class
+// A thread with a reservation may run for its budget once every period
+// and the thread with the earliest deadline is always selected first.
+// Threads without a reservation are left to the best effort scheduler
+// and only run when no thread with a reservation is ready. A thread
+// that uses up its budget is throttled until its next period starts
+
+// Utilisation is measured in millionths of the processor
+pub const FULL_UTILISATION: usize = 1_000_000;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Reservation {
+	/// The length of a period in ticks
+	pub period: usize,
+	/// The ticks the thread may run for in each period
+	pub budget: usize,
+	/// The tick at which the current period ends
+	pub deadline: usize,
+	/// The ticks left in the current period
+	pub remaining: usize,
+}
+
+impl Reservation {
+	/// Creates a reservation whose first period starts at the tick
+	///
+	/// Returns None if the deadline or the utilisation would overflow
+	pub fn new(period: usize, budget: usize, tick: usize) -> Option<Reservation> {
+		let scaled_budget = budget.checked_mul(FULL_UTILISATION).and_then(|budget| budget.checked_add(period));
+		match (tick.checked_add(period), scaled_budget) {
+			(Some(deadline), Some(_)) => Some(Reservation {
+				period,
+				budget,
+				deadline,
+				remaining: budget,
+			}),
+			_ => None,
+		}
+	}
+
+	/// The share of the processor that the reservation needs, rounded up
+	pub fn utilisation(&self) -> usize {
+		(self.budget * FULL_UTILISATION + self.period - 1) / self.period
+	}
+
+	/// Starts the next period if the current one has ended
+	fn replenish(&mut self, tick: usize) {
+		if tick >= self.deadline {
+			self.deadline = self.deadline.saturating_add(self.period).max(tick.saturating_add(self.period));
+			self.remaining = self.budget;
+		}
+	}
+}
+
+/// Returns the total utilisation with the reservation added, or None
+/// if the reservations could no longer all meet their deadlines
+pub fn admit(admitted: usize, reservation: &Reservation) -> Option<usize> {
+	if reservation.budget == 0 || reservation.budget > reservation.period {
+		return None;
+	}
+
+	let utilisation = admitted + reservation.utilisation();
+	match utilisation <= FULL_UTILISATION {
+		true => Some(utilisation),
+		false => None,
+	}
+}
+
+pub struct EarliestDeadline {
+	ready: Vec<Thread>,
+	throttled: Vec<Thread>,
+	best_effort: Box<Scheduler + Send>,
+}
+
+impl EarliestDeadline {
+	pub fn new(best_effort: Box<Scheduler + Send>) -> EarliestDeadline {
+		EarliestDeadline {
+			ready: Vec::new(),
+			throttled: Vec::new(),
+			best_effort,
+		}
+	}
+
+	/// Moves the throttled threads whose next period has started
+	fn release(&mut self, tick: usize) {
+		let mut index = 0;
+		while index < self.throttled.len() {
+			if tick >= reservation(&self.throttled[index]).deadline {
+				let mut thread = self.throttled.remove(index);
+				reservation_mut(&mut thread).replenish(tick);
+				self.ready.push(thread);
+			} else {
+				index += 1;
+			}
+		}
+
+		// Threads that missed their deadline start their next period
+		for thread in self.ready.iter_mut() {
+			reservation_mut(thread).replenish(tick);
+		}
+	}
+
+	fn earliest_deadline(&self) -> Option<(usize, usize)> {
+		self.ready.iter()
+		          .map(|thread| reservation(thread).deadline)
+		          .enumerate()
+		          .min_by_key(|&(_, deadline)| deadline)
+	}
+}
+
+impl Scheduler for EarliestDeadline {
+	fn schedule_next(&mut self) -> Option<Thread> {
+		self.release(::task::functions::ticks());
+		match self.earliest_deadline() {
+			Some((index, _)) => Some(self.ready.remove(index)),
+			None => self.best_effort.schedule_next(),
+		}
+	}
+
+	fn schedule_new(&mut self, mut new_thread: Thread) {
+		if new_thread.reservation.is_none() {
+			return self.best_effort.schedule_new(new_thread);
+		}
+
+		let throttled = {
+			let reservation = reservation_mut(&mut new_thread);
+			reservation.replenish(::task::functions::ticks());
+			reservation.remaining == 0
+		};
+
+		match throttled {
+			true => self.throttled.push(new_thread),
+			false => self.ready.push(new_thread),
+		}
+	}
+
+	fn requeue(&mut self, thread: Thread) {
+		match thread.reservation {
+			Some(_) => self.schedule_new(thread),
+			None => self.best_effort.requeue(thread),
+		}
+	}
+
+	fn remove(&mut self, id: ThreadId) -> Option<Thread> {
+		if let Some(index) = self.ready.iter().position(|thread| thread.id == id) {
+			return Some(self.ready.remove(index));
+		}
+
+		if let Some(index) = self.throttled.iter().position(|thread| thread.id == id) {
+			return Some(self.throttled.remove(index));
+		}
+		self.best_effort.remove(id)
+	}
+
+	fn with_thread(&mut self, id: ThreadId, function: &mut FnMut(&mut Thread)) -> bool {
+		let thread = self.ready.iter_mut().chain(self.throttled.iter_mut()).find(|thread| thread.id == id);
+		match thread {
+			Some(thread) => {
+				function(thread);
+				true
+			}
+			None => self.best_effort.with_thread(id, function),
+		}
+	}
+
+	fn next_release(&self) -> Option<usize> {
+		self.throttled.iter().map(|thread| reservation(thread).deadline).min()
+	}
+
+	fn for_each(&self, function: &mut FnMut(&Thread)) {
+		self.ready.iter().chain(self.throttled.iter()).for_each(&mut *function);
+		self.best_effort.for_each(function);
+	}
+
+	fn tick(&mut self, active_thread: &mut Thread) -> bool {
+		let tick = ::task::functions::ticks();
+		self.release(tick);
+
+		let earliest_deadline = self.earliest_deadline().map(|(_, deadline)| deadline);
+
+		// Best effort threads are preempted as soon as
+		// a thread with a reservation is ready
+		if active_thread.reservation.is_none() {
+			return self.best_effort.tick(active_thread) || earliest_deadline.is_some();
+		}
+
+		let reservation = reservation_mut(active_thread);
+		reservation.remaining = reservation.remaining.saturating_sub(1);
+		reservation.replenish(tick);
+		reservation.remaining == 0 || earliest_deadline.map_or(false, |deadline| deadline < reservation.deadline)
+	}
+}
+
+fn reservation(thread: &Thread) -> &Reservation {
+	thread.reservation.as_ref().expect("Thread has no reservation")
+}
+
+fn reservation_mut(thread: &mut Thread) -> &mut Reservation {
+	thread.reservation.as_mut().expect("Thread has no reservation")
+}
@@ -1,5 +1,38 @@
+pub use self::earliest_deadline::EarliestDeadline;
+pub use self::earliest_deadline::Reservation;
+pub use self::multilevel_feedback::MultilevelFeedback;
+pub use self::priority::PriorityScheduler;
 pub use self::round_robin::RoundRobin;
+use alloc::boxed::Box;
 use super::Scheduler;
 use super::Thread;
+use super::ThreadId;
 
+pub mod earliest_deadline;
+pub mod multilevel_feedback;
+pub mod priority;
 pub mod round_robin;
+
+/// Creates the scheduler named by the scheduler boot option
+///
+/// Threads with a reservation are always scheduled first by
+/// the earliest deadline class, see earliest_deadline
+pub fn create_scheduler(name: Option<&str>) -> Box<Scheduler + Send> {
+	box EarliestDeadline::new(create_best_effort(name))
+}
+
+fn create_best_effort(name: Option<&str>) -> Box<Scheduler + Send> {
+	match name {
+		Some("priority") => box PriorityScheduler::new(),
+		Some("mlfq") => {
+			// Threads are boosted once a second
+			let boost_period = ::interrupts::pit_functions::frequency() as usize;
+			box MultilevelFeedback::new(boost_period)
+		}
+		Some("round_robin") | None => box RoundRobin::new(),
+		Some(name) => {
+			eprintln!("Unknown scheduler: {}, using round_robin", name);
+			box RoundRobin::new()
+		}
+	}
+}
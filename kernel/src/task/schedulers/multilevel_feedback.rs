@@ -0,0 +1,110 @@
+use alloc::VecDeque;
+use super::Thread;
+use super::ThreadId;
+
+// A multi-level feedback queue scheduler
+// Threads start in the first level, which is always selected first
+// A thread that uses its whole quantum is moved down a level, where
+// the quantum is longer, and a thread that blocks or yields before
+// the end of its quantum is moved up a level. Every thread is moved
+// back to the first level periodically so that none starve
+
+pub const LEVEL_COUNT: usize = 4;
+
+// The quantum doubles with each level
+const QUANTUM_TICKS: [usize; LEVEL_COUNT] = [1, 2, 4, 8];
+
+pub struct MultilevelFeedback {
+	levels: [VecDeque<Thread>; LEVEL_COUNT],
+	boost_period: usize,
+	boost_ticks: usize,
+}
+
+impl MultilevelFeedback {
+	/// Creates a scheduler that boosts every thread
+	/// once every boost period, in ticks
+	pub fn new(boost_period: usize) -> MultilevelFeedback {
+		MultilevelFeedback {
+			levels: Default::default(),
+			boost_period,
+			boost_ticks: 0,
+		}
+	}
+
+	fn boost(&mut self) {
+		let (first, rest) = self.levels.split_at_mut(1);
+		for level in rest.iter_mut() {
+			first[0].extend(level.drain(..));
+		}
+
+		// Parked threads are not boosted, but they move
+		// up a level anyway when they are woken
+		for thread in first[0].iter_mut() {
+			thread.level = 0;
+			thread.slice_ticks = 0;
+		}
+	}
+}
+
+impl super::Scheduler for MultilevelFeedback {
+	fn schedule_next(&mut self) -> Option<Thread> {
+		self.levels.iter_mut()
+		           .filter_map(|level| level.pop_front())
+		           .next()
+	}
+
+	fn schedule_new(&mut self, mut new_thread: Thread) {
+		// Threads that are preempted before the end of their quantum
+		// are requeued instead, so any other thread blocked or yielded
+		// early. See task/functions::switch_thread
+		let level = new_thread.level.min(LEVEL_COUNT - 1);
+		new_thread.level = if new_thread.slice_ticks >= QUANTUM_TICKS[level] {
+			(level + 1).min(LEVEL_COUNT - 1)
+		} else {
+			level.saturating_sub(1)
+		};
+
+		new_thread.slice_ticks = 0;
+		let level = new_thread.level;
+		self.levels[level].push_back(new_thread);
+	}
+
+	fn requeue(&mut self, thread: Thread) {
+		// The thread keeps its level and the ticks it has used
+		let level = thread.level.min(LEVEL_COUNT - 1);
+		self.levels[level].push_back(thread);
+	}
+
+	fn remove(&mut self, id: ThreadId) -> Option<Thread> {
+		for level in self.levels.iter_mut() {
+			if let Some(index) = level.iter().position(|thread| thread.id == id) {
+				return level.remove(index);
+			}
+		}
+		None
+	}
+
+	fn with_thread(&mut self, id: ThreadId, function: &mut FnMut(&mut Thread)) -> bool {
+		self.levels.iter_mut().flat_map(|level| level.iter_mut())
+		           .find(|thread| thread.id == id).map(function).is_some()
+	}
+
+	fn for_each(&self, function: &mut FnMut(&Thread)) {
+		self.levels.iter().flat_map(|queue| queue.iter()).for_each(function);
+	}
+
+	fn tick(&mut self, active_thread: &mut Thread) -> bool {
+		self.boost_ticks += 1;
+		if self.boost_ticks >= self.boost_period {
+			self.boost_ticks = 0;
+			self.boost();
+			active_thread.level = 0;
+			active_thread.slice_ticks = 0;
+			return true;
+		}
+
+		active_thread.slice_ticks += 1;
+		let level = active_thread.level.min(LEVEL_COUNT - 1);
+		active_thread.slice_ticks >= QUANTUM_TICKS[level]
+	}
+}
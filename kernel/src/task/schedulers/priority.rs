@@ -0,0 +1,57 @@
+use alloc::VecDeque;
+use super::Thread;
+use super::ThreadId;
+use task::thread::Priority;
+use task::thread::PRIORITY_COUNT;
+
+// A fixed priority scheduler
+// The next thread is taken from the highest priority queue that is
+// not empty, so lower priority threads only run when every higher
+// priority thread is parked. Threads of the same priority take turns
+
+pub struct PriorityScheduler {
+	queues: [VecDeque<Thread>; PRIORITY_COUNT],
+}
+
+impl PriorityScheduler {
+	pub fn new() -> PriorityScheduler {
+		PriorityScheduler {
+			queues: Default::default(),
+		}
+	}
+
+	fn queue(&mut self, priority: Priority) -> &mut VecDeque<Thread> {
+		&mut self.queues[priority.min(PRIORITY_COUNT - 1)]
+	}
+}
+
+impl super::Scheduler for PriorityScheduler {
+	fn schedule_next(&mut self) -> Option<Thread> {
+		self.queues.iter_mut().rev()
+		           .filter_map(|queue| queue.pop_front())
+		           .next()
+	}
+
+	fn schedule_new(&mut self, new_thread: Thread) {
+		let priority = new_thread.priority;
+		self.queue(priority).push_back(new_thread);
+	}
+
+	fn remove(&mut self, id: ThreadId) -> Option<Thread> {
+		for queue in self.queues.iter_mut() {
+			if let Some(index) = queue.iter().position(|thread| thread.id == id) {
+				return queue.remove(index);
+			}
+		}
+		None
+	}
+
+	fn with_thread(&mut self, id: ThreadId, function: &mut FnMut(&mut Thread)) -> bool {
+		self.queues.iter_mut().flat_map(|queue| queue.iter_mut())
+		           .find(|thread| thread.id == id).map(function).is_some()
+	}
+
+	fn for_each(&self, function: &mut FnMut(&Thread)) {
+		self.queues.iter().flat_map(|queue| queue.iter()).for_each(function);
+	}
+}
@@ -1,5 +1,6 @@
 use alloc::VecDeque;
 use super::Thread;
+use super::ThreadId;
 
 // The simplest scheduler possible
 // The next thread is the thread pushed on earliest
@@ -25,4 +26,17 @@ impl super::Scheduler for RoundRobin {
 	fn schedule_new(&mut self, new_thread: Thread) {
 		self.threads.push_back(new_thread);
 	}
+
+	fn remove(&mut self, id: ThreadId) -> Option<Thread> {
+		let index = self.threads.iter().position(|thread| thread.id == id)?;
+		self.threads.remove(index)
+	}
+
+	fn with_thread(&mut self, id: ThreadId, function: &mut FnMut(&mut Thread)) -> bool {
+		self.threads.iter_mut().find(|thread| thread.id == id).map(function).is_some()
+	}
+
+	fn for_each(&self, function: &mut FnMut(&Thread)) {
+		self.threads.iter().for_each(function);
+	}
 }
\ No newline at end of file
@@ -0,0 +1,396 @@
+use alloc::Vec;
+use core::mem::size_of;
+use paging::user_access;
+use paging::VirtualAddress;
+use system_call::SystemCallFrame;
+use x86_64::structures::idt::ExceptionStackFrame;
+use super::extended_state::ExtendedState;
+use super::functions;
+
+// Signals notify a user mode thread of an event. Every thread has a set
+// of pending signals and a mask of blocked signals, which stay pending
+// until they are unblocked. A pending signal is delivered when the thread
+// next returns to user mode: at the end of a system call, after a fault
+// or when the thread is resumed after being interrupted in user mode.
+//
+// A signal either terminates the thread, is ignored or calls a handler
+// that the thread registered. The handler is called on the user stack
+// with a SignalFrame below it, whose return address is the restorer
+// given with the handler. The restorer must make the sigreturn system
+// call with int 0xaa, which restores the registers from the frame.
+//
+// Faults in user mode raise SIGSEGV, SIGILL or SIGFPE instead of
+// panicking. See interrupts/handlers
+
+pub type Signal = usize;
+
+// Signals are numbered from one, like their bits in a mask
+pub const SIGNAL_COUNT: usize = 32;
+
+pub const SIGHUP: Signal = 1;
+pub const SIGINT: Signal = 2;
+pub const SIGQUIT: Signal = 3;
+pub const SIGILL: Signal = 4;
+pub const SIGTRAP: Signal = 5;
+pub const SIGABRT: Signal = 6;
+pub const SIGBUS: Signal = 7;
+pub const SIGFPE: Signal = 8;
+pub const SIGKILL: Signal = 9;
+pub const SIGUSR1: Signal = 10;
+pub const SIGSEGV: Signal = 11;
+pub const SIGUSR2: Signal = 12;
+pub const SIGPIPE: Signal = 13;
+pub const SIGALRM: Signal = 14;
+pub const SIGTERM: Signal = 15;
+pub const SIGCHLD: Signal = 17;
+pub const SIGURG: Signal = 23;
+pub const SIGWINCH: Signal = 28;
+
+// Handlers that do not return with sigreturn leave their floating point
+// registers behind, so only the most recent ones are kept
+const MAX_SAVED_STATES: usize = 8;
+
+// The flags that a thread may change, the rest are kept on sigreturn
+// Carry, Parity, Adjust, Zero, Sign, Trap, Direction, Overflow
+const USER_FLAGS: u64 = 0b1101_1101_0101;
+const TRAP_FLAG: u64 = 1 << 8;
+const DIRECTION_FLAG: u64 = 1 << 10;
+// Interrupt Enable Flag, Reserved
+const R_FLAGS: u64 = 0b10_0000_0010;
+
+// The System V ABI lets functions use the area below the stack pointer
+const RED_ZONE_SIZE: u64 = 128;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SignalAction {
+	Default,
+	Ignore,
+	/// The handler is called with the signal and returns to the restorer
+	Handler { handler: u64, restorer: u64 },
+}
+
+/// The pending and blocked signals of a thread and how it handles them
+pub struct SignalState {
+	pub pending: u64,
+	pub blocked: u64,
+	actions: [SignalAction; SIGNAL_COUNT],
+	/// The floating point registers of the code that a handler interrupted
+	saved_states: Vec<ExtendedState>,
+	/// The user mode context of a fault, see on_user_fault
+	fault: Option<ReturnFrame>,
+}
+
+impl SignalState {
+	pub fn new() -> SignalState {
+		SignalState {
+			pending: 0,
+			blocked: 0,
+			actions: [SignalAction::Default; SIGNAL_COUNT],
+			saved_states: Vec::new(),
+			fault: None,
+		}
+	}
+
+	/// Creates the state of a forked thread, which has
+	/// the same handlers but no pending signals
+	pub fn fork(&self) -> SignalState {
+		SignalState {
+			blocked: self.blocked,
+			actions: self.actions,
+			..SignalState::new()
+		}
+	}
+
+	/// Handlers are removed when a thread starts a new program
+	/// while ignored signals stay ignored
+	pub fn reset_handlers(&mut self) {
+		for action in self.actions.iter_mut() {
+			if let SignalAction::Handler { .. } = *action {
+				*action = SignalAction::Default;
+			}
+		}
+		self.saved_states.clear();
+	}
+
+	pub fn action(&self, signal: Signal) -> SignalAction {
+		self.actions[signal]
+	}
+
+	/// Returns false if the action of the signal cannot be changed
+	pub fn set_action(&mut self, signal: Signal, action: SignalAction) -> bool {
+		if signal == SIGKILL {
+			return false;
+		}
+
+		self.actions[signal] = action;
+		true
+	}
+
+	pub fn set_blocked(&mut self, blocked: u64) {
+		self.blocked = blocked & !mask(SIGKILL);
+	}
+
+	pub fn raise(&mut self, signal: Signal) {
+		self.pending |= mask(signal);
+	}
+
+	/// Raises a signal that cannot be blocked or ignored, as
+	/// the thread cannot continue without handling it
+	///
+	/// A blocked signal is usually being handled already, so its
+	/// handler faulted and the thread is terminated instead
+	pub fn force(&mut self, signal: Signal) {
+		let blocked = self.blocked & mask(signal) != 0;
+		if blocked || self.actions[signal] == SignalAction::Ignore {
+			self.actions[signal] = SignalAction::Default;
+		}
+		self.blocked &= !mask(signal);
+		self.raise(signal);
+	}
+
+	pub fn has_deliverable(&self) -> bool {
+		self.pending & !self.blocked != 0
+	}
+
+	/// Takes the lowest pending signal that is not blocked or ignored
+	/// Default means that the signal terminates the thread
+	fn take_next(&mut self) -> Option<(Signal, SignalAction)> {
+		while self.has_deliverable() {
+			let signal = (self.pending & !self.blocked).trailing_zeros() as Signal + 1;
+			self.pending &= !mask(signal);
+
+			if !self.is_ignored(signal) {
+				return Some((signal, self.actions[signal]));
+			}
+		}
+		None
+	}
+
+	/// Returns true if a pending signal will be delivered rather than
+	/// ignored. Such a signal interrupts blocking system calls
+	pub fn is_interrupted(&self) -> bool {
+		let deliverable = self.pending & !self.blocked;
+		(1..SIGNAL_COUNT).any(|signal| deliverable & mask(signal) != 0 && !self.is_ignored(signal))
+	}
+
+	fn is_ignored(&self, signal: Signal) -> bool {
+		match self.actions[signal] {
+			SignalAction::Ignore => true,
+			SignalAction::Default => ignored_by_default(signal),
+			SignalAction::Handler { .. } => false,
+		}
+	}
+}
+
+pub fn is_valid(signal: usize) -> bool {
+	signal > 0 && signal < SIGNAL_COUNT
+}
+
+fn mask(signal: Signal) -> u64 {
+	1 << (signal - 1)
+}
+
+fn ignored_by_default(signal: Signal) -> bool {
+	match signal {
+		SIGCHLD | SIGURG | SIGWINCH => true,
+		_ => false,
+	}
+}
+
+/// The exit code of a thread terminated by the signal
+pub fn exit_code(signal: Signal) -> u64 {
+	128 + signal as u64
+}
+
+/// The part of a SystemCallFrame that the processor pushes
+/// It has the same layout as an ExceptionStackFrame
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct ReturnFrame {
+	instruction_pointer: u64,
+	code_segment: u64,
+	cpu_flags: u64,
+	stack_pointer: u64,
+	stack_segment: u64,
+}
+
+/// Written to the user stack when a handler is called
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct SignalFrame {
+	pub return_address: u64,
+	pub signal: u64,
+	/// The blocked signals before the handler was called
+	pub blocked: u64,
+	pub context: SystemCallFrame,
+}
+
+/// Delivers the pending signals of the active thread before
+/// it returns to user mode with the frame
+///
+/// Does not return if a signal terminates the thread
+pub fn deliver_pending(frame: &mut SystemCallFrame) {
+	let next = functions::with_active_thread(|thread| thread.signals.take_next());
+	match next {
+		None => (),
+		Some((signal, SignalAction::Handler { handler, restorer })) => {
+			// Only one handler is called at a time, the others
+			// are delivered when it returns with sigreturn
+			if call_handler(frame, signal, handler, restorer).is_err() {
+				functions::exit(exit_code(SIGSEGV));
+			}
+		}
+		Some((signal, _)) => functions::exit(exit_code(signal)),
+	}
+}
+
+/// Returns true if the active thread has a signal to deliver, in which
+/// case blocking system calls return early. See functions::send_signal
+pub fn is_interrupted() -> bool {
+	functions::with_active_thread(|thread| thread.signals.is_interrupted())
+}
+
+fn call_handler(frame: &mut SystemCallFrame, signal: Signal, handler: u64, restorer: u64)
+                -> user_access::UserAccessResult<()> {
+	// The signal is blocked while its handler runs and the floating
+	// point registers are saved in case the handler changes them
+	let blocked = functions::with_active_thread(|thread| {
+		let blocked = thread.signals.blocked;
+		thread.signals.set_blocked(blocked | mask(signal));
+
+		thread.extended_state.synchronize(thread.id);
+		let state = thread.extended_state.duplicate();
+		let saved_states = &mut thread.signals.saved_states;
+		if saved_states.len() == MAX_SAVED_STATES {
+			saved_states.remove(0);
+		}
+		saved_states.push(state);
+		blocked
+	});
+
+	let signal_frame = SignalFrame {
+		return_address: restorer,
+		signal: signal as u64,
+		blocked,
+		context: frame.clone(),
+	};
+
+	// The handler starts as if it was called, so the stack pointer is
+	// 16 byte aligned once the return address has been pushed
+	let address = frame.stack_pointer.wrapping_sub(RED_ZONE_SIZE + size_of::<SignalFrame>() as u64);
+	let address = (address & !0xf).wrapping_sub(size_of::<u64>() as u64);
+	let data = ::utility::convert::as_u8_slice(&[signal_frame]);
+	user_access::copy_to_user(&VirtualAddress::new(address as usize), data)?;
+
+	frame.instruction_pointer = handler;
+	frame.stack_pointer = address;
+	frame.rdi = signal as u64;
+	frame.cpu_flags &= !(TRAP_FLAG | DIRECTION_FLAG);
+	Ok(())
+}
+
+/// Restores the registers that were saved when a handler was called
+///
+/// The stack pointer of the frame is just above the return
+/// address of the handler, which the restorer does not touch
+pub fn restore(frame: &mut SystemCallFrame) -> user_access::UserAccessResult<()> {
+	let address = VirtualAddress::new(frame.stack_pointer.wrapping_sub(size_of::<u64>() as u64) as usize);
+	let data = user_access::read_buffer(&address, size_of::<SignalFrame>())?;
+	let signal_frame = unsafe { ::core::ptr::read_unaligned(data.as_ptr() as *const SignalFrame) };
+
+	// The sysret instruction faults in kernel mode on a
+	// non canonical address, so the frame must stay in user space
+	let context = signal_frame.context;
+	if context.instruction_pointer > ::paging::reserved::USER_SPACE_TOP.raw() as u64 {
+		return Err(user_access::UserAccessError::OutOfBounds);
+	}
+
+	// The thread cannot change its segments or privileged flags
+	*frame = SystemCallFrame {
+		code_segment: frame.code_segment,
+		stack_segment: frame.stack_segment,
+		cpu_flags: (context.cpu_flags & USER_FLAGS) | R_FLAGS,
+		..context
+	};
+
+	functions::with_active_thread(|thread| {
+		thread.signals.set_blocked(signal_frame.blocked);
+		if let Some(state) = thread.signals.saved_states.pop() {
+			thread.extended_state.release(thread.id);
+			thread.extended_state = state;
+		}
+	});
+	Ok(())
+}
+
+/// Called by a fault handler when the fault occurred in user mode
+///
+/// The handler returns to interrupts/handlers::user_fault_entry at the
+/// top of the kernel stack of the thread, where the registers of the
+/// thread are saved and on_fault_entry delivers the signal
+pub fn on_user_fault(stack_frame: &mut ExceptionStackFrame, signal: Signal) {
+	use core::ptr::{read_volatile, write_volatile};
+	use paging::PageLike;
+
+	let return_frame = stack_frame as *mut ExceptionStackFrame as *mut ReturnFrame;
+	let fault = unsafe { read_volatile(return_frame) };
+	let kernel_stack_top = functions::with_active_thread(|thread| {
+		thread.signals.force(signal);
+		thread.signals.fault = Some(fault);
+		thread.kernel_stack.end_address().raw() + 1
+	});
+
+	let entry = ::interrupts::handlers::user_fault_entry as unsafe extern "C" fn();
+	unsafe {
+		write_volatile(return_frame, ReturnFrame {
+			instruction_pointer: entry as u64,
+			code_segment: *::interrupts::functions::KERNEL_CODE_SELECTOR.try().unwrap() as u64,
+			cpu_flags: 0b10,
+			stack_pointer: kernel_stack_top as u64,
+			stack_segment: *::interrupts::functions::KERNEL_DATA_SELECTOR.try().unwrap() as u64,
+		});
+	}
+}
+
+/// Called from interrupts/handlers::user_fault_entry with the
+/// registers of the thread that faulted
+pub extern "C" fn on_fault_entry(frame: &mut SystemCallFrame) {
+	let fault = functions::with_active_thread(|thread| thread.signals.fault.take())
+		.expect("No fault to deliver");
+	frame.instruction_pointer = fault.instruction_pointer;
+	frame.code_segment = fault.code_segment;
+	frame.cpu_flags = fault.cpu_flags;
+	frame.stack_pointer = fault.stack_pointer;
+	frame.stack_segment = fault.stack_segment;
+	on_signal_entry(frame);
+}
+
+/// Called from interrupts/handlers::signal_entry
+pub extern "C" fn on_signal_entry(frame: &mut SystemCallFrame) {
+	// The thread runs in kernel mode like during a system call
+	// so it may be preempted and it may exit
+	unsafe { ::x86_64::instructions::interrupts::enable(); }
+	deliver_pending(frame);
+}
+
+/// Makes a thread that was interrupted in user mode deliver its
+/// signals before it returns there. Returns the new stack pointer
+///
+/// Called from task/functions::switch_thread once the
+/// kernel stack of the thread has been switched to
+pub fn redirect(stack_pointer: usize) -> usize {
+	use super::loaders::stack;
+
+	let frame = unsafe { &*(stack_pointer as *const SystemCallFrame) };
+	if frame.code_segment & 0b11 != 0b11 {
+		return stack_pointer;
+	}
+
+	// The thread resumes in kernel mode with the stack
+	// pointer at its frame, like a forked thread does
+	let entry = VirtualAddress::new(::interrupts::handlers::signal_entry as usize);
+	let stack_data = stack::create_kernel_stack(&entry, &VirtualAddress::new(stack_pointer));
+	let stack_pointer = stack_pointer - stack::INITIAL_STACK_SIZE * size_of::<u64>();
+	unsafe { *(stack_pointer as *mut [u64; stack::INITIAL_STACK_SIZE]) = stack_data; }
+	stack_pointer
+}
@@ -0,0 +1,47 @@
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use super::MutexGuard;
+use task::WaitQueue;
+
+pub struct Condvar {
+	// Every notification changes the generation, so a thread that
+	// unlocks its mutex and is notified before it is parked sees
+	// that the generation changed and does not park
+	generation: AtomicUsize,
+	waiters: WaitQueue,
+}
+
+impl Condvar {
+	pub const fn new() -> Condvar {
+		Condvar {
+			generation: AtomicUsize::new(0),
+			waiters: WaitQueue::new(),
+		}
+	}
+
+	/// Unlocks the mutex and parks the active thread until it is
+	/// notified, then locks the mutex again
+	///
+	/// The thread can be resumed without being notified,
+	/// so the caller must check its condition again
+	pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+		let generation = self.generation.load(Ordering::SeqCst);
+		let mutex = guard.mutex();
+		drop(guard);
+
+		super::wait_until(&self.waiters, || self.generation.load(Ordering::SeqCst) != generation);
+		mutex.lock()
+	}
+
+	/// Wakes one waiting thread
+	pub fn notify_one(&self) {
+		self.generation.fetch_add(1, Ordering::SeqCst);
+		self.waiters.wake_one();
+	}
+
+	/// Wakes every waiting thread
+	pub fn notify_all(&self) {
+		self.generation.fetch_add(1, Ordering::SeqCst);
+		self.waiters.wake_all();
+	}
+}
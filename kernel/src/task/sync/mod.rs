@@ -0,0 +1,28 @@
+pub use self::condvar::Condvar;
+pub use self::mutex::Mutex;
+pub use self::mutex::MutexGuard;
+pub use self::semaphore::Semaphore;
+
+use core::sync::atomic::spin_loop_hint;
+use super::WaitQueue;
+
+pub mod mutex;
+pub mod semaphore;
+pub mod condvar;
+
+// Unlike spin::Mutex, these primitives park the active thread on a
+// wait queue while they are contended so that the thread holding
+// them can run. They must not be used from interrupt handlers
+
+/// Parks the active thread on the queue until the condition holds
+///
+/// Before the scheduler has started there is no thread to
+/// park, so the condition is polled instead
+fn wait_until<F>(queue: &WaitQueue, condition: F) where F: Fn() -> bool {
+	match super::functions::can_block() {
+		true => queue.wait_until(condition),
+		false => while !condition() {
+			spin_loop_hint();
+		},
+	}
+}
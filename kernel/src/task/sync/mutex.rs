@@ -0,0 +1,81 @@
+use core::cell::UnsafeCell;
+use core::ops::Deref;
+use core::ops::DerefMut;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+use task::WaitQueue;
+
+pub struct Mutex<T> {
+	locked: AtomicBool,
+	waiters: WaitQueue,
+	data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+	pub const fn new(data: T) -> Mutex<T> {
+		Mutex {
+			locked: AtomicBool::new(false),
+			waiters: WaitQueue::new(),
+			data: UnsafeCell::new(data),
+		}
+	}
+
+	/// Locks the mutex, parking the active thread while it is held
+	pub fn lock(&self) -> MutexGuard<T> {
+		loop {
+			if let Some(guard) = self.try_lock() {
+				return guard;
+			}
+			super::wait_until(&self.waiters, || !self.locked.load(Ordering::SeqCst));
+		}
+	}
+
+	/// Locks the mutex if it is not held
+	pub fn try_lock(&self) -> Option<MutexGuard<T>> {
+		match self.locked.compare_and_swap(false, true, Ordering::SeqCst) {
+			false => Some(MutexGuard { mutex: self }),
+			true => None,
+		}
+	}
+
+	pub fn into_inner(self) -> T {
+		self.data.into_inner()
+	}
+}
+
+pub struct MutexGuard<'a, T: 'a> {
+	mutex: &'a Mutex<T>,
+}
+
+impl<'a, T> MutexGuard<'a, T> {
+	/// The mutex that the guard unlocks
+	pub fn mutex(&self) -> &'a Mutex<T> {
+		self.mutex
+	}
+}
+
+impl<'a, T> Deref for MutexGuard<'a, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		unsafe { &*self.mutex.data.get() }
+	}
+}
+
+impl<'a, T> DerefMut for MutexGuard<'a, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		unsafe { &mut *self.mutex.data.get() }
+	}
+}
+
+impl<'a, T> Drop for MutexGuard<'a, T> {
+	fn drop(&mut self) {
+		// The woken thread competes for the mutex with any
+		// thread that locks it before the woken thread runs
+		self.mutex.locked.store(false, Ordering::SeqCst);
+		self.mutex.waiters.wake_one();
+	}
+}
@@ -0,0 +1,49 @@
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use task::WaitQueue;
+
+pub struct Semaphore {
+	count: AtomicUsize,
+	waiters: WaitQueue,
+}
+
+impl Semaphore {
+	pub const fn new(count: usize) -> Semaphore {
+		Semaphore {
+			count: AtomicUsize::new(count),
+			waiters: WaitQueue::new(),
+		}
+	}
+
+	/// Takes a unit, parking the active thread until one is available
+	pub fn acquire(&self) {
+		while !self.try_acquire() {
+			super::wait_until(&self.waiters, || self.count.load(Ordering::SeqCst) > 0);
+		}
+	}
+
+	/// Takes a unit if one is available
+	pub fn try_acquire(&self) -> bool {
+		let mut count = self.count.load(Ordering::SeqCst);
+		while count > 0 {
+			let previous = self.count.compare_and_swap(count, count - 1, Ordering::SeqCst);
+			if previous == count {
+				return true;
+			}
+			count = previous;
+		}
+		false
+	}
+
+	/// Returns a unit and wakes a thread waiting for one
+	///
+	/// Unlike acquire, this can be called from interrupt handlers
+	pub fn release(&self) {
+		self.count.fetch_add(1, Ordering::SeqCst);
+		self.waiters.wake_one();
+	}
+
+	pub fn count(&self) -> usize {
+		self.count.load(Ordering::SeqCst)
+	}
+}
@@ -1,12 +1,141 @@
+use alloc::boxed::Box;
+use alloc::BTreeSet;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
 use paging::InactivePageTable;
 use paging::Page;
 use paging::VirtualAddress;
+use super::extended_state::ExtendedState;
+use super::HandleTable;
+use super::kernel_stack::KernelStack;
+use super::schedulers::Reservation;
+use super::signal::SignalState;
+
+pub type ThreadId = usize;
+
+// Identifiers start at one as zero is reserved for the idle thread
+pub const IDLE_THREAD_ID: ThreadId = 0;
+static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(1);
+
+// Threads with a higher priority are selected first by
+// schedulers that use priorities. See task/schedulers
+pub type Priority = usize;
+pub const PRIORITY_COUNT: usize = 8;
+pub const DEFAULT_PRIORITY: Priority = 3;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ThreadState {
+	Ready,
+	/// Parked until the tick count reaches the value
+	Sleeping(usize),
+	/// Parked until the child thread exits
+	Waiting(ThreadId),
+	/// Parked until woken, see task::WaitQueue
+	/// Only interruptible threads are woken by signals
+	Blocked { interruptible: bool },
+	/// Kept until the parent collects the exit code
+	Exited(u64),
+}
+
+/// The processor time used by a thread, in ticks
+/// See task/functions::switch_thread
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ThreadStatistics {
+	pub running_ticks: usize,
+	pub scheduled_count: usize,
+	/// Switches caused by the thread yielding or blocking
+	pub voluntary_switches: usize,
+	/// Switches caused by the timer
+	pub preemptive_switches: usize,
+	pub blocked_ticks: usize,
+	pub blocked_since: Option<usize>,
+}
+
+impl ThreadStatistics {
+	/// The ticks spent blocked including the current block
+	pub fn blocked_ticks(&self, ticks: usize) -> usize {
+		self.blocked_ticks + self.blocked_since.map_or(0, |since| ticks - since)
+	}
+}
 
-#[derive(Debug, Clone)]
 pub struct Thread {
+	pub id: ThreadId,
+	pub parent: Option<ThreadId>,
+	pub children: BTreeSet<ThreadId>,
+	pub state: ThreadState,
+	pub priority: Priority,
+	/// The queue and used ticks of the thread in
+	/// schedulers::MultilevelFeedback
+	pub level: usize,
+	pub slice_ticks: usize,
+	/// Threads with a reservation are scheduled by deadline
+	pub reservation: Option<Reservation>,
 	pub page_table: InactivePageTable,
 	pub kernel_stack: Page,
 	pub stack_pointer: VirtualAddress,
+	pub handles: HandleTable,
+	/// The function and stack of a kernel thread
+	/// See task/functions::spawn_kernel
+	pub kernel_entry: Option<Box<FnMut() + Send>>,
+	pub kernel_thread_stack: Option<KernelStack>,
+	pub extended_state: ExtendedState,
+	/// The thread pointer of the thread local storage
+	/// See task/loaders/tls
+	pub fs_base: u64,
+	/// See task/signal
+	pub signals: SignalState,
+	pub statistics: ThreadStatistics,
+}
+
+impl Thread {
+	pub fn new(page_table: InactivePageTable, kernel_stack: Page, stack_pointer: VirtualAddress) -> Thread {
+		let id = NEXT_THREAD_ID.fetch_add(1, Ordering::SeqCst);
+		Thread::with_id(id, page_table, kernel_stack, stack_pointer)
+	}
+
+	/// Creates the thread that runs when no other thread is ready
+	pub fn new_idle(page_table: InactivePageTable, kernel_stack: Page, stack_pointer: VirtualAddress) -> Thread {
+		Thread::with_id(IDLE_THREAD_ID, page_table, kernel_stack, stack_pointer)
+	}
+
+	fn with_id(id: ThreadId, page_table: InactivePageTable, kernel_stack: Page,
+	           stack_pointer: VirtualAddress) -> Thread {
+		Thread {
+			id,
+			parent: None,
+			children: BTreeSet::new(),
+			state: ThreadState::Ready,
+			priority: DEFAULT_PRIORITY,
+			level: 0,
+			slice_ticks: 0,
+			reservation: None,
+			page_table,
+			kernel_stack,
+			stack_pointer,
+			handles: HandleTable::new(),
+			kernel_entry: None,
+			kernel_thread_stack: None,
+			extended_state: ExtendedState::new(),
+			fs_base: 0,
+			signals: SignalState::new(),
+			statistics: ThreadStatistics::default(),
+		}
+	}
+
+	pub fn is_idle(&self) -> bool {
+		self.id == IDLE_THREAD_ID
+	}
 }
 
-impl Thread {}
\ No newline at end of file
+// Host tests create threads without an address space,
+// so nothing is released when they are dropped
+#[cfg(not(test))]
+impl Drop for Thread {
+	fn drop(&mut self) {
+		self.extended_state.release(self.id);
+
+		// The kernel stack and every other user space
+		// frame is freed along with the page table
+		::paging::teardown::free_table(self.page_table.clone());
+	}
+}
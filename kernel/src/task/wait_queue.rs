@@ -0,0 +1,154 @@
+use alloc::VecDeque;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use interrupts::functions::without_interrupts;
+use spin::Mutex;
+use super::functions;
+use super::ThreadId;
+use super::ThreadState;
+
+// Threads park on a wait queue until another thread or an interrupt
+// handler wakes them. A woken thread is not guaranteed that the
+// condition it waited for still holds, so waiters use wait_until
+
+pub struct WaitQueue {
+	// The queue is created on first use so that
+	// wait queues can be used in statics
+	threads: Mutex<Option<VecDeque<ThreadId>>>,
+	// The number of queued threads, which lets a wake on an
+	// empty queue return without disabling interrupts
+	length: AtomicUsize,
+}
+
+impl WaitQueue {
+	pub const fn new() -> WaitQueue {
+		WaitQueue {
+			threads: Mutex::new(None),
+			length: AtomicUsize::new(0),
+		}
+	}
+
+	/// Parks the active thread until the condition holds
+	///
+	/// The condition is checked with interrupts disabled
+	/// and must not use the active thread
+	pub fn wait_until<F>(&self, condition: F) where F: Fn() -> bool {
+		use core::cell::Cell;
+		let satisfied = Cell::new(false);
+		while !satisfied.get() {
+			// The condition is only checked while registering, so it may
+			// take locks shared with interrupt handlers and a wake from
+			// an interrupt handler after the check is not lost
+			let registered = Cell::new(None);
+			functions::block_on(ThreadState::Blocked { interruptible: false }, |id| {
+				if condition() {
+					satisfied.set(true);
+					return false;
+				}
+
+				self.push(id);
+				registered.set(Some(id));
+				true
+			});
+
+			// A thread woken some other way is still queued and would
+			// be queued twice, so a later wake could be given to it
+			if let Some(id) = registered.get() {
+				self.remove(id);
+			}
+		}
+	}
+
+	/// Parks the active thread once if the condition holds until it
+	/// is woken through the queue, the tick count reaches the timeout
+	/// or it has a signal to deliver
+	///
+	/// The condition is checked with interrupts disabled. Returns
+	/// false if the condition did not hold or the thread was
+	/// woken by anything other than the queue
+	pub fn wait_once<F>(&self, condition: F, timeout: Option<usize>) -> bool where F: FnOnce() -> bool {
+		use core::cell::Cell;
+		let registered = Cell::new(None);
+		let register = |id: ThreadId| {
+			if !condition() {
+				return false;
+			}
+
+			self.push(id);
+			registered.set(Some(id));
+			true
+		};
+
+		match timeout {
+			Some(wake_tick) => functions::block_on_until(wake_tick, register),
+			None => functions::block_on_interruptible(ThreadState::Blocked { interruptible: true }, register),
+		}
+
+		// Threads that are woken through the queue are no longer in it
+		match registered.get() {
+			Some(id) => !self.remove(id),
+			None => false,
+		}
+	}
+
+	fn push(&self, id: ThreadId) {
+		self.threads.lock().get_or_insert_with(VecDeque::new).push_back(id);
+		self.length.fetch_add(1, Ordering::SeqCst);
+	}
+
+	/// Removes the thread from the queue
+	///
+	/// Returns false if it is not queued
+	fn remove(&self, id: ThreadId) -> bool {
+		without_interrupts(|| {
+			let mut threads = self.threads.lock();
+			let threads = threads.as_mut().expect("Registered thread not queued");
+			match threads.iter().position(|thread| *thread == id) {
+				Some(index) => {
+					threads.remove(index);
+					self.length.fetch_sub(1, Ordering::SeqCst);
+					true
+				}
+				None => false,
+			}
+		})
+	}
+
+	/// Wakes the thread that has waited the longest
+	///
+	/// Returns false if no thread was waiting
+	pub fn wake_one(&self) -> bool {
+		loop {
+			// Threads are queued with interrupts disabled, so a thread
+			// cannot be queued between this check and the state change
+			// made by the caller before waking
+			if self.length.load(Ordering::SeqCst) == 0 {
+				return false;
+			}
+
+			let thread = without_interrupts(|| {
+				let thread = self.threads.lock().as_mut().and_then(|threads| threads.pop_front());
+				if thread.is_some() {
+					self.length.fetch_sub(1, Ordering::SeqCst);
+				}
+				thread
+			});
+			match thread {
+				// The thread may have been woken already
+				Some(thread) => if functions::wake(thread) {
+					return true;
+				},
+				None => return false,
+			}
+		}
+	}
+
+	/// Wakes every waiting thread and returns how many were woken
+	pub fn wake_all(&self) -> usize {
+		let mut count = 0;
+		while self.wake_one() {
+			count += 1;
+		}
+		count
+	}
+}
@@ -0,0 +1,55 @@
+use debug::lockdep::*;
+
+const OWNER: usize = 1;
+const FIRST_TRACE: Trace = [1; TRACE_DEPTH];
+const SECOND_TRACE: Trace = [2; TRACE_DEPTH];
+
+#[test]
+fn test_consistent_order() {
+	let mut dependencies = LockDependencies::new();
+	for _ in 0..2 {
+		assert_eq!(dependencies.acquire("FIRST", OWNER, FIRST_TRACE), None);
+		assert_eq!(dependencies.acquire("SECOND", OWNER, SECOND_TRACE), None);
+		dependencies.release("SECOND", OWNER);
+		dependencies.release("FIRST", OWNER);
+	}
+}
+
+#[test]
+fn test_inversion() {
+	let mut dependencies = LockDependencies::new();
+	dependencies.acquire("FIRST", OWNER, FIRST_TRACE);
+	dependencies.acquire("SECOND", OWNER, FIRST_TRACE);
+	dependencies.release("SECOND", OWNER);
+	dependencies.release("FIRST", OWNER);
+
+	let first = dependencies.class("FIRST").unwrap();
+	let second = dependencies.class("SECOND").unwrap();
+	assert_eq!(dependencies.acquire("SECOND", OWNER, SECOND_TRACE), None);
+	assert_eq!(dependencies.acquire("FIRST", OWNER, SECOND_TRACE),
+	           Some(Violation::Inversion { class: first, held: second, previous: FIRST_TRACE }));
+	dependencies.release("FIRST", OWNER);
+	dependencies.release("SECOND", OWNER);
+
+	// Each inversion is only reported once
+	dependencies.acquire("SECOND", OWNER, SECOND_TRACE);
+	assert_eq!(dependencies.acquire("FIRST", OWNER, SECOND_TRACE), None);
+}
+
+#[test]
+fn test_recursion() {
+	let mut dependencies = LockDependencies::new();
+	let first = dependencies.class("FIRST").unwrap();
+	assert_eq!(dependencies.acquire("FIRST", OWNER, FIRST_TRACE), None);
+	assert_eq!(dependencies.acquire("FIRST", OWNER, SECOND_TRACE),
+	           Some(Violation::Recursion { class: first, previous: FIRST_TRACE }));
+}
+
+#[test]
+fn test_separate_owners() {
+	// Globals held by other threads do not create dependencies
+	let mut dependencies = LockDependencies::new();
+	dependencies.acquire("FIRST", OWNER, FIRST_TRACE);
+	assert_eq!(dependencies.acquire("SECOND", OWNER + 1, SECOND_TRACE), None);
+	assert_eq!(dependencies.acquire("FIRST", OWNER + 1, SECOND_TRACE), None);
+}
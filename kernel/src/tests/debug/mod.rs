@@ -0,0 +1,2 @@
+#[cfg(debug_assertions)]
+mod lockdep;
\ No newline at end of file
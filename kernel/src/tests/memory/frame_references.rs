@@ -0,0 +1,21 @@
+#[test]
+fn test_frame_references() {
+	use memory::Frame;
+	use memory::FrameLike;
+	use memory::frame_references::FrameReferences;
+
+	let mut references = FrameReferences::new();
+	let frame = Frame::from_index(10);
+	let other = Frame::from_index(11);
+	assert_eq!(references.count(&frame), 1);
+
+	references.share(&frame);
+	references.share(&frame);
+	assert_eq!(references.count(&frame), 3);
+	assert_eq!(references.count(&other), 1);
+
+	assert!(!references.release(&frame));
+	assert!(!references.release(&frame));
+	assert_eq!(references.count(&frame), 1);
+	assert!(references.release(&frame));
+}
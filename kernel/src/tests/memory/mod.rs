@@ -1,2 +1,3 @@
 mod memory_area;
-mod fixed_frame_recycler;
\ No newline at end of file
+mod fixed_frame_recycler;
+mod frame_references;
\ No newline at end of file
@@ -1,4 +1,7 @@
 mod display;
 mod structures;
 mod memory;
-mod utility;
\ No newline at end of file
+mod utility;
+mod task;
+mod debug;
+mod paging;
\ No newline at end of file
@@ -0,0 +1 @@
+mod user_access;
\ No newline at end of file
@@ -0,0 +1,59 @@
+use paging::EntryFlags;
+use paging::Page;
+use paging::PageLike;
+use paging::VirtualAddress;
+use paging::reserved::USER_SPACE_TOP;
+use paging::user_access::*;
+
+const PAGE_SIZE: usize = Page::SIZE as usize;
+const MAPPED: usize = 0x40_0000;
+
+// Three pages are mapped at MAPPED, of which the second is read only,
+// followed by an unmapped page and then a page that is writable
+fn lookup(address: &VirtualAddress) -> Option<(EntryFlags, u64)> {
+	let user = EntryFlags::PRESENT | EntryFlags::USER_ACCESSIBLE;
+	match (address.raw() - MAPPED) / PAGE_SIZE {
+		0 | 2 | 4 => Some((user | EntryFlags::WRITABLE, Page::SIZE)),
+		1 => Some((user, Page::SIZE)),
+		_ => None,
+	}
+}
+
+fn check(address: usize, size: usize, writable: bool) -> UserAccessResult<()> {
+	check_range(&VirtualAddress::new(address), size, writable, lookup)
+}
+
+#[test]
+fn test_mapped_range() {
+	assert_eq!(check(MAPPED + 8, 3 * PAGE_SIZE - 16, false), Ok(()));
+	assert_eq!(check(MAPPED, PAGE_SIZE, true), Ok(()));
+	assert_eq!(check(MAPPED + 3 * PAGE_SIZE, 0, true), Ok(()));
+}
+
+#[test]
+fn test_unmapped_page() {
+	assert_eq!(check(MAPPED + 2 * PAGE_SIZE, 2 * PAGE_SIZE + 1, false), Err(UserAccessError::NotAccessible));
+	assert_eq!(check(MAPPED + 3 * PAGE_SIZE + 8, 8, false), Err(UserAccessError::NotAccessible));
+}
+
+#[test]
+fn test_read_only_page() {
+	assert_eq!(check(MAPPED + PAGE_SIZE, 8, false), Ok(()));
+	assert_eq!(check(MAPPED + PAGE_SIZE - 4, 8, true), Err(UserAccessError::NotAccessible));
+}
+
+#[test]
+fn test_out_of_bounds() {
+	let top = USER_SPACE_TOP.raw();
+	assert_eq!(check(top - 7, 16, false), Err(UserAccessError::OutOfBounds));
+	assert_eq!(check(usize::max_value() - 7, 16, false), Err(UserAccessError::OutOfBounds));
+	assert_eq!(check(MAPPED, usize::max_value(), false), Err(UserAccessError::OutOfBounds));
+}
+
+#[test]
+fn test_huge_page() {
+	// The whole huge page is covered by its entry
+	let huge = |_: &VirtualAddress| Some((EntryFlags::PRESENT | EntryFlags::USER_ACCESSIBLE, 512 * Page::SIZE));
+	let address = VirtualAddress::new(0x20_0000);
+	assert_eq!(check_range(&address, 512 * PAGE_SIZE, false, huge), Ok(()));
+}
@@ -0,0 +1,24 @@
+use task::schedulers::earliest_deadline::*;
+
+#[test]
+fn test_admission() {
+	let half = Reservation::new(10, 5, 0).unwrap();
+	let quarter = Reservation::new(8, 2, 0).unwrap();
+	let admitted = admit(0, &half).unwrap();
+	let admitted = admit(admitted, &quarter).unwrap();
+	assert_eq!(admitted, FULL_UTILISATION * 3 / 4);
+	assert_eq!(admit(admitted, &Reservation::new(3, 1, 0).unwrap()), None);
+	assert_eq!(admit(admitted, &quarter), Some(FULL_UTILISATION));
+}
+
+#[test]
+fn test_invalid_reservation() {
+	assert_eq!(admit(0, &Reservation::new(4, 0, 0).unwrap()), None);
+	assert_eq!(admit(0, &Reservation::new(4, 5, 0).unwrap()), None);
+}
+
+#[test]
+fn test_overflowing_reservation() {
+	assert_eq!(Reservation::new(2, 1, usize::max_value() - 1), None);
+	assert_eq!(Reservation::new(usize::max_value(), usize::max_value() / 2, 0), None);
+}
@@ -0,0 +1,62 @@
+use alloc::Vec;
+use task::loaders::elf_binary::*;
+
+fn write_value(binary: &mut Vec<u8>, offset: usize, size: usize, value: u64) {
+	for index in 0..size {
+		binary[offset + index] = (value >> (index * 8)) as u8;
+	}
+}
+
+fn create_binary(virtual_address: u64, memory_size: u64) -> Vec<u8> {
+	let mut binary = vec![0; 64 + 56 + 16];
+	binary[0..4].copy_from_slice(b"\x7fELF");
+	binary[4] = 2;
+	binary[5] = 1;
+	binary[6] = 1;
+	write_value(&mut binary, 16, 2, 2);
+	write_value(&mut binary, 18, 2, 0x3e);
+	write_value(&mut binary, 24, 8, virtual_address);
+	write_value(&mut binary, 32, 8, 64);
+	write_value(&mut binary, 54, 2, 56);
+	write_value(&mut binary, 56, 2, 1);
+
+	write_value(&mut binary, 64, 4, ProgramHeader::LOAD as u64);
+	write_value(&mut binary, 68, 4, ProgramHeader::EXECUTABLE as u64);
+	write_value(&mut binary, 72, 8, 120);
+	write_value(&mut binary, 80, 8, virtual_address);
+	write_value(&mut binary, 96, 8, 16);
+	write_value(&mut binary, 104, 8, memory_size);
+	binary
+}
+
+#[test]
+fn test_valid_binary() {
+	let binary = create_binary(0x40_0000, 0x2000);
+	let header = parse_header(&binary).unwrap();
+	assert_eq!(header.entry_point, 0x40_0000);
+
+	let segments = parse_segments(&binary, &header).unwrap();
+	assert_eq!(segments.len(), 1);
+	assert_eq!(segments[0].end_address(), 0x40_2000);
+	assert!(segments[0].entry_flags().contains(::paging::EntryFlags::USER_ACCESSIBLE));
+	assert!(!segments[0].entry_flags().contains(::paging::EntryFlags::NO_EXECUTE));
+}
+
+#[test]
+fn test_malformed_binary() {
+	let mut binary = create_binary(0x40_0000, 0x2000);
+	binary[0] = 0;
+	assert_eq!(parse_header(&binary).unwrap_err(), ElfError::InvalidMagic);
+	assert_eq!(parse_header(&binary[..32]).unwrap_err(), ElfError::Truncated);
+
+	let binary = create_binary(0x40_0000, 8);
+	let header = parse_header(&binary).unwrap();
+	assert_eq!(parse_segments(&binary, &header).unwrap_err(), ElfError::InvalidSegment);
+}
+
+#[test]
+fn test_kernel_segment() {
+	let binary = create_binary(0x7fff_ffff_f000, 0x2000);
+	let header = parse_header(&binary).unwrap();
+	assert_eq!(parse_segments(&binary, &header).unwrap_err(), ElfError::KernelSegment);
+}
@@ -0,0 +1,27 @@
+use memory::Frame;
+use memory::FrameLike;
+use memory::PhysicalAddress;
+use paging::InactivePageTable;
+use paging::Page;
+use paging::PageLike;
+use paging::VirtualAddress;
+use task::Thread;
+
+mod elf_binary;
+mod earliest_deadline;
+mod multilevel_feedback;
+mod priority;
+mod signal;
+mod stack;
+mod sync;
+mod tls;
+
+/// Creates a thread for scheduler tests that is never run
+///
+/// Threads release nothing when dropped in tests (see task/thread),
+/// so the page table only needs a frame that is never accessed
+pub fn create_thread() -> Thread {
+	let table = unsafe { InactivePageTable::new_mapped(Frame::from_address(PhysicalAddress::new(0))) };
+	let address = VirtualAddress::new(0);
+	Thread::new(table, Page::from_address(address.clone()), address)
+}
\ No newline at end of file
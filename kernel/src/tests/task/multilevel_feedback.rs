@@ -0,0 +1,81 @@
+use super::create_thread;
+use task::Scheduler;
+use task::schedulers::multilevel_feedback::*;
+use task::ThreadId;
+
+const BOOST_PERIOD: usize = 10;
+
+fn next_level(scheduler: &mut MultilevelFeedback) -> Option<(ThreadId, usize)> {
+	scheduler.schedule_next().map(|thread| (thread.id, thread.level))
+}
+
+#[test]
+fn test_demotion() {
+	let mut scheduler = MultilevelFeedback::new(BOOST_PERIOD);
+	let mut thread = create_thread();
+	let demoted_id = thread.id;
+
+	// The first level has a quantum of one tick
+	assert!(scheduler.tick(&mut thread));
+	scheduler.schedule_new(thread);
+
+	let fresh = create_thread();
+	let fresh_id = fresh.id;
+	scheduler.schedule_new(fresh);
+
+	assert_eq!(next_level(&mut scheduler), Some((fresh_id, 0)));
+	assert_eq!(next_level(&mut scheduler), Some((demoted_id, 1)));
+	assert_eq!(next_level(&mut scheduler), None);
+}
+
+#[test]
+fn test_boost() {
+	let mut scheduler = MultilevelFeedback::new(BOOST_PERIOD);
+	let mut thread = create_thread();
+	let id = thread.id;
+	thread.level = LEVEL_COUNT - 1;
+	thread.slice_ticks = usize::max_value();
+	scheduler.schedule_new(thread);
+
+	let mut active = create_thread();
+	active.level = LEVEL_COUNT - 1;
+	for _ in 0..BOOST_PERIOD - 1 {
+		scheduler.tick(&mut active);
+	}
+	assert_eq!(active.level, LEVEL_COUNT - 1);
+
+	scheduler.tick(&mut active);
+	assert_eq!(active.level, 0);
+	assert_eq!(next_level(&mut scheduler), Some((id, 0)));
+}
+
+#[test]
+fn test_with_thread() {
+	let mut scheduler = MultilevelFeedback::new(BOOST_PERIOD);
+	let mut demoted = create_thread();
+	let demoted_id = demoted.id;
+	demoted.slice_ticks = usize::max_value();
+	scheduler.schedule_new(demoted);
+
+	// The thread keeps its level rather than being demoted again
+	let mut called = false;
+	assert!(scheduler.with_thread(demoted_id, &mut |thread| called = thread.level == 1));
+	assert!(called);
+	assert!(!scheduler.with_thread(demoted_id + 1, &mut |_| panic!("Thread is not queued")));
+	assert_eq!(next_level(&mut scheduler), Some((demoted_id, 1)));
+}
+
+#[test]
+fn test_requeue() {
+	let mut scheduler = MultilevelFeedback::new(BOOST_PERIOD);
+	let mut thread = create_thread();
+	let id = thread.id;
+	thread.level = 2;
+	scheduler.schedule_new(thread);
+
+	// A thread that is removed and queued again keeps its level
+	let thread = scheduler.remove(id).unwrap();
+	assert_eq!(thread.level, 1);
+	scheduler.requeue(thread);
+	assert_eq!(next_level(&mut scheduler), Some((id, 1)));
+}
@@ -0,0 +1,32 @@
+use task::Scheduler;
+use task::schedulers::priority::*;
+use task::Thread;
+use task::ThreadId;
+
+fn create_thread(priority: usize) -> Thread {
+	let mut thread = super::create_thread();
+	thread.priority = priority;
+	thread
+}
+
+fn next_id(scheduler: &mut PriorityScheduler) -> Option<ThreadId> {
+	scheduler.schedule_next().map(|thread| thread.id)
+}
+
+#[test]
+fn test_priority_order() {
+	let mut scheduler = PriorityScheduler::new();
+	let low = create_thread(1);
+	let high = create_thread(5);
+	let other_high = create_thread(5);
+	let (low_id, high_id, other_high_id) = (low.id, high.id, other_high.id);
+	scheduler.schedule_new(low);
+	scheduler.schedule_new(high);
+	scheduler.schedule_new(other_high);
+
+	// Threads of the same priority are selected in turn
+	assert_eq!(next_id(&mut scheduler), Some(high_id));
+	assert_eq!(next_id(&mut scheduler), Some(other_high_id));
+	assert_eq!(next_id(&mut scheduler), Some(low_id));
+	assert_eq!(next_id(&mut scheduler), None);
+}
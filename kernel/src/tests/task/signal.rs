@@ -0,0 +1,32 @@
+use task::signal::*;
+
+#[test]
+fn test_signal_state() {
+	let mut state = SignalState::new();
+	assert!(!state.set_action(SIGKILL, SignalAction::Ignore));
+	assert!(state.set_action(SIGSEGV, SignalAction::Ignore));
+
+	state.set_blocked(!0);
+	state.raise(SIGSEGV);
+	assert!(!state.has_deliverable());
+
+	// Faults are delivered even when blocked or ignored
+	state.force(SIGSEGV);
+	assert!(state.has_deliverable());
+	assert_eq!(state.action(SIGSEGV), SignalAction::Default);
+
+	// A handler is kept unless the fault happens while it runs
+	let handler = SignalAction::Handler { handler: 0x1000, restorer: 0x2000 };
+	state.set_blocked(0);
+	state.set_action(SIGSEGV, handler);
+	state.force(SIGSEGV);
+	assert_eq!(state.action(SIGSEGV), handler);
+	state.set_blocked(!0);
+	state.force(SIGSEGV);
+	assert_eq!(state.action(SIGSEGV), SignalAction::Default);
+
+	state.raise(SIGKILL);
+	let child = state.fork();
+	assert_eq!(child.pending, 0);
+	assert_eq!(child.blocked, state.blocked);
+}
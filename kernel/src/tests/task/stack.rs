@@ -0,0 +1,29 @@
+use alloc::String;
+use paging::VirtualAddress;
+use task::loaders::stack::*;
+
+fn read_word(data: &[u8], index: usize) -> u64 {
+	(0..8).fold(0, |value, byte| value | (data[index * 8 + byte] as u64) << (byte * 8))
+}
+
+#[test]
+fn test_argument_stack() {
+	let stack_top = VirtualAddress::new(0x1000);
+	let arguments = [String::from("init"), String::from("-v")];
+	let environment = [String::from("PATH=/")];
+	let (stack_pointer, data) = create_argument_stack(&stack_top, &arguments, &environment, &[(AT_PAGESZ, 4096)]);
+	assert_eq!(stack_pointer.raw() % 16, 0);
+	assert_eq!(stack_pointer.raw() + data.len(), stack_top.raw());
+
+	let offset = |pointer: u64| pointer as usize - stack_pointer.raw();
+	assert_eq!(read_word(&data, 0), 2);
+	assert_eq!(&data[offset(read_word(&data, 1))..][..5], b"init\0");
+	assert_eq!(&data[offset(read_word(&data, 2))..][..3], b"-v\0");
+	assert_eq!(read_word(&data, 3), 0);
+	assert_eq!(&data[offset(read_word(&data, 4))..][..7], b"PATH=/\0");
+	assert_eq!(read_word(&data, 5), 0);
+	assert_eq!(read_word(&data, 6), AT_PAGESZ);
+	assert_eq!(read_word(&data, 7), 4096);
+	assert_eq!(read_word(&data, 8), AT_NULL);
+	assert_eq!(read_word(&data, 9), 0);
+}
@@ -0,0 +1,26 @@
+use task::sync::*;
+
+// Only the uncontended paths can be tested here. A contender parks
+// through the scheduler, which needs an active thread and disables
+// interrupts, so parking is only exercised when running the kernel
+
+#[test]
+fn test_mutex() {
+	let mutex = Mutex::new(1);
+	{
+		let mut guard = mutex.lock();
+		*guard += 1;
+		assert!(mutex.try_lock().is_none());
+	}
+	assert_eq!(*mutex.try_lock().unwrap(), 2);
+}
+
+#[test]
+fn test_semaphore() {
+	let semaphore = Semaphore::new(1);
+	assert!(semaphore.try_acquire());
+	assert!(!semaphore.try_acquire());
+	semaphore.release();
+	semaphore.acquire();
+	assert_eq!(semaphore.count(), 0);
+}
@@ -0,0 +1,38 @@
+use task::loaders::tls::*;
+
+#[test]
+fn test_tls_block() {
+	let template = TlsTemplate {
+		image: vec![1, 2, 3],
+		memory_size: 20,
+		alignment: 32,
+	};
+
+	let (thread_pointer, block_start, data) = create_tls_block(&template, 0x1_0000);
+	assert_eq!(thread_pointer.raw() % 32, 0);
+	assert!(thread_pointer.raw() + CONTROL_BLOCK_SIZE <= 0x1_0000);
+	assert_eq!(block_start.raw(), thread_pointer.raw() - 32);
+	assert_eq!(&data[..4], &[1, 2, 3, 0]);
+
+	let control_block = &data[thread_pointer.raw() - block_start.raw()..];
+	assert_eq!(control_block.len(), CONTROL_BLOCK_SIZE);
+	let self_pointer = control_block[..8].iter().rev().fold(0, |value, byte| (value << 8) | *byte as usize);
+	assert_eq!(self_pointer, thread_pointer.raw());
+}
+
+#[test]
+fn test_small_alignment() {
+	let template = TlsTemplate {
+		image: vec![1, 2, 3, 4],
+		memory_size: 20,
+		alignment: 4,
+	};
+
+	// The data starts at the thread pointer minus the size rounded
+	// up to the alignment of the segment rather than sixteen bytes
+	let (thread_pointer, block_start, data) = create_tls_block(&template, 0x1_0000);
+	assert_eq!(thread_pointer.raw() % 16, 0);
+	assert_eq!(block_start.raw(), thread_pointer.raw() - 20);
+	assert_eq!(&data[..5], &[1, 2, 3, 4, 0]);
+	assert_eq!(data.len(), 20 + CONTROL_BLOCK_SIZE);
+}
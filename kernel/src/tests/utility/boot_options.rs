@@ -0,0 +1,10 @@
+use utility::BootOptions;
+
+#[test]
+fn test_parse() {
+	let options = BootOptions::parse(" scheduler=priority  quiet path=/a=b ");
+	assert_eq!(options.get("scheduler"), Some("priority"));
+	assert_eq!(options.get("quiet"), Some(""));
+	assert_eq!(options.get("path"), Some("/a=b"));
+	assert_eq!(options.get("missing"), None);
+}
@@ -1 +1,2 @@
 mod math;
+mod boot_options;
\ No newline at end of file
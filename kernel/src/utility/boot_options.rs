@@ -0,0 +1,36 @@
+use alloc::BTreeMap;
+use alloc::String;
+use multiboot2::BootInformation;
+
+// Options are passed on the kernel command line as whitespace
+// separated key=value pairs. For example, in grub.cfg:
+//      multiboot2 /boot/kernel.bin scheduler=priority
+pub struct BootOptions {
+	options: BTreeMap<String, String>,
+}
+
+impl BootOptions {
+	pub fn parse(command_line: &str) -> BootOptions {
+		let options = command_line.split_whitespace().map(|option| {
+			let mut parts = option.splitn(2, '=');
+			let key = parts.next().unwrap_or("");
+			let value = parts.next().unwrap_or("");
+			(String::from(key), String::from(value))
+		}).collect();
+
+		BootOptions {
+			options,
+		}
+	}
+
+	pub fn from_boot_information(boot_information: &BootInformation) -> BootOptions {
+		let command_line = boot_information.command_line_tag().map(|tag| tag.command_line());
+		BootOptions::parse(command_line.unwrap_or(""))
+	}
+
+	/// Returns the value of the option, which is empty
+	/// if the option was given without one
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.options.get(key).map(|value| value.as_str())
+	}
+}
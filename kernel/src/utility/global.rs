@@ -1,8 +1,16 @@
+use interrupts::functions::InterruptGuard;
 use spin::Mutex;
 use spin::MutexGuard;
 
+// Globals that are locked by interrupt handlers must be created with
+// new_irq_safe. Otherwise, an interrupt handler that locks the global
+// while the code it interrupted holds it spins forever. Interrupts
+// are disabled while an interrupt safe global is locked and debug
+// builds panic when any other global is locked by an interrupt handler
+
 pub struct Global<T> {
 	identifier: &'static str,
+	irq_safe: bool,
 	object: Mutex<Option<T>>,
 }
 
@@ -10,6 +18,16 @@ impl<T> Global<T> {
 	pub const fn new(identifier: &'static str) -> Global<T> {
 		Global {
 			identifier,
+			irq_safe: false,
+			object: Mutex::new(None),
+		}
+	}
+
+	/// Creates a global that can be locked by interrupt handlers
+	pub const fn new_irq_safe(identifier: &'static str) -> Global<T> {
+		Global {
+			identifier,
+			irq_safe: true,
 			object: Mutex::new(None),
 		}
 	}
@@ -17,24 +35,49 @@ impl<T> Global<T> {
 	pub fn set(&self, object: T) {
 		use core::ops::DerefMut;
 		use core::mem::replace;
+		let _interrupts = self.disable_interrupts();
 		replace(self.object.lock().deref_mut(), Some(object));
 	}
 
-	pub fn lock_direct(&self) -> MutexGuard<Option<T>> {
-		self.object.lock()
+	/// Locks the global without disabling interrupts
+	/// or checking that it is initialized
+	#[cfg(not(debug_assertions))]
+	pub fn lock_direct(&self) -> DirectGuard<T> {
+		DirectGuard {
+			guard: self.object.lock(),
+		}
+	}
+
+	#[cfg(debug_assertions)]
+	pub fn lock_direct(&self) -> DirectGuard<T> {
+		::debug::lockdep::acquire(self.identifier);
+		DirectGuard {
+			guard: self.object.lock(),
+			identifier: self.identifier,
+		}
 	}
 
 	#[cfg(not(debug_assertions))]
 	pub fn lock(&self) -> GlobalGuard<T> {
+		let interrupts = self.disable_interrupts();
 		let guard = self.object.lock();
 		if guard.is_none() { panic!("Global not initialized: {}", self.identifier); }
 		GlobalGuard {
 			guard,
+			_interrupts: interrupts,
 		}
 	}
 
 	#[cfg(debug_assertions)]
 	pub fn lock(&self) -> GlobalGuard<T> {
+		if !self.irq_safe && ::interrupts::functions::in_interrupt() {
+			panic!("Global locked in interrupt handler: {}", self.identifier);
+		}
+
+		// Lock order problems are reported before the lock is taken
+		// as taking it may never finish. See debug/lockdep
+		let interrupts = self.disable_interrupts();
+		::debug::lockdep::acquire(self.identifier);
 		GlobalGuard {
 			// Our kernel runs on one CPU so any contention results
 			// in a dead lock (until the scheduler has been created)
@@ -48,12 +91,33 @@ impl<T> Global<T> {
 					self.object.lock()
 				}
 			},
+			_interrupts: interrupts,
+			identifier: self.identifier,
+		}
+	}
+
+	fn disable_interrupts(&self) -> Option<InterruptGuard> {
+		match self.irq_safe {
+			true => Some(::interrupts::functions::disable_interrupts()),
+			false => None,
 		}
 	}
 }
 
 pub struct GlobalGuard<'a, T: 'a> {
+	// Fields are dropped in order, so the global
+	// is unlocked before interrupts are enabled
 	guard: MutexGuard<'a, Option<T>>,
+	_interrupts: Option<InterruptGuard>,
+	#[cfg(debug_assertions)]
+	identifier: &'static str,
+}
+
+#[cfg(debug_assertions)]
+impl<'a, T> Drop for GlobalGuard<'a, T> {
+	fn drop(&mut self) {
+		::debug::lockdep::release(self.identifier);
+	}
 }
 
 impl<'a, T> ::core::ops::Deref for GlobalGuard<'a, T> {
@@ -69,3 +133,30 @@ impl<'a, T> ::core::ops::DerefMut for GlobalGuard<'a, T> {
 		self.guard.deref_mut().as_mut().unwrap()
 	}
 }
+
+pub struct DirectGuard<'a, T: 'a> {
+	guard: MutexGuard<'a, Option<T>>,
+	#[cfg(debug_assertions)]
+	identifier: &'static str,
+}
+
+#[cfg(debug_assertions)]
+impl<'a, T> Drop for DirectGuard<'a, T> {
+	fn drop(&mut self) {
+		::debug::lockdep::release(self.identifier);
+	}
+}
+
+impl<'a, T> ::core::ops::Deref for DirectGuard<'a, T> {
+	type Target = Option<T>;
+
+	fn deref(&self) -> &<Self as ::core::ops::Deref>::Target {
+		self.guard.deref()
+	}
+}
+
+impl<'a, T> ::core::ops::DerefMut for DirectGuard<'a, T> {
+	fn deref_mut(&mut self) -> &mut <Self as ::core::ops::Deref>::Target {
+		self.guard.deref_mut()
+	}
+}
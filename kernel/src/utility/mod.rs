@@ -1,7 +1,9 @@
+pub use self::boot_options::BootOptions;
 pub use self::global::Global;
 pub use self::multiboot_structure::MultibootStructure;
 pub use self::pseudo_random::PseudoRandomGenerator;
 
+pub mod boot_options;
 pub mod math;
 pub mod convert;
 pub mod global;